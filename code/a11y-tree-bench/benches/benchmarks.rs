@@ -0,0 +1,518 @@
+use a11y_tree_core::{
+    lazy::LazyTree, shard::ShardedTree, A11yNode, Scenario, Step, Tree, TreeConfig, TreeCount,
+    TreeTraversal,
+};
+use atspi_common::Role;
+use criterion::{
+    black_box, criterion_group, criterion_main, measurement::Measurement, BatchSize,
+    BenchmarkGroup, Criterion, Throughput,
+};
+use rayon::iter::ParallelIterator;
+use serde_json::from_str;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const SYNTH_FN: &str = "../../data/synthetic.json";
+const REAL_FN: &str = "../../data/single-page-html-spec.json";
+/// Directory of [`Scenario`]s recorded with `repl --record`; each one is replayed as a benchmark.
+const SCENARIO_DIR: &str = "./scenarios";
+/// How many subtrees are appended in a burst of writes, like a page load.
+const BURST: usize = 200;
+/// How many copies of the real data's largest application make up a multi-app desktop.
+const APPS: usize = 4;
+
+fn seq_bench<M: Measurement, T: TreeTraversal>(mut g: BenchmarkGroup<'_, M>, t: &T, synth: bool) {
+    g.throughput(Throughput::Elements(1_u64));
+    g.sample_size(200);
+    if synth {
+        g.measurement_time(Duration::from_secs(150));
+    } else {
+        g.measurement_time(Duration::from_secs(30));
+    }
+    g.bench_function("find_first", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.find_first(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("iter_leafs", |b| {
+        b.iter(|| {
+            t.iter_leafs().for_each(|x| {
+                black_box(x);
+            });
+        })
+    });
+    g.bench_function("how_many", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.how_many(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("how_many_roleset", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.how_many_roleset(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("max_dpeth", |b| {
+        b.iter(|| {
+            let x = t.max_depth();
+            black_box(x);
+        })
+    });
+    g.bench_function("unique_roles", |b| {
+        b.iter(|| {
+            let x = t.unique_roles();
+            black_box(x);
+        })
+    });
+    g.bench_function("unique_roles_roleset", |b| {
+        b.iter(|| {
+            let x = t.unique_roles_roleset();
+            black_box(x);
+        })
+    });
+    g.bench_function("find_first_roleset", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.find_first_roleset(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("find_first_stack", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.find_first_stack(role);
+            black_box(x);
+        })
+    });
+    g.finish()
+}
+/// Benchmarks the first occurrence table, which only [`Tree`] keeps; `t` must have its rolesets
+/// built, or this only measures the fallback.
+fn cached_bench<M: Measurement>(mut g: BenchmarkGroup<'_, M>, t: &Tree, synth: bool) {
+    assert!(t.config().rolesets, "First occurrences are indexed");
+    g.throughput(Throughput::Elements(1_u64));
+    g.sample_size(200);
+    if synth {
+        g.measurement_time(Duration::from_secs(150));
+    } else {
+        g.measurement_time(Duration::from_secs(30));
+    }
+    g.bench_function("find_first_cached", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.find_first_cached(role);
+            black_box(x);
+        })
+    });
+    g.finish()
+}
+fn par_bench<M: Measurement, T: TreeTraversal>(mut g: BenchmarkGroup<'_, M>, t: &T, synth: bool) {
+    g.throughput(Throughput::Elements(1_u64));
+    g.sample_size(200);
+    if synth {
+        g.measurement_time(Duration::from_secs(60));
+    } else {
+        g.measurement_time(Duration::from_secs(15));
+    }
+    g.bench_function("par_iter_leafs", |b| {
+        b.iter(|| {
+            t.par_iter_leafs().for_each(|x| {
+                black_box(x);
+            });
+        })
+    });
+    g.bench_function("par_how_many", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.par_how_many(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("par_how_many_roleset", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.par_how_many_roleset(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("par_max_dpeth", |b| {
+        b.iter(|| {
+            let x = t.par_max_depth();
+            black_box(x);
+        })
+    });
+    g.bench_function("par_unique_roles", |b| {
+        b.iter(|| {
+            let x = t.par_unique_roles();
+            black_box(x);
+        })
+    });
+    g.bench_function("par_find_first", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.par_find_first(role);
+            black_box(x);
+        })
+    });
+    g.bench_function("par_find_first_roleset", |b| {
+        b.iter(|| {
+            // technically black box knowledge here; the largest item ID = 129
+            let role_id = rand::random_range(0..=129);
+            let role = Role::try_from(role_id).expect("Valid role ID!");
+            let x = t.par_find_first_roleset(role);
+            black_box(x);
+        })
+    });
+    g.finish()
+}
+
+fn scenario_bench<M: Measurement, T: TreeTraversal>(
+    mut g: BenchmarkGroup<'_, M>,
+    t: &T,
+    steps: &[Step],
+) {
+    g.throughput(Throughput::Elements(steps.len() as u64));
+    g.bench_function("replay", |b| {
+        b.iter(|| {
+            for step in steps {
+                let x = step.run(t);
+                black_box(x);
+            }
+        })
+    });
+    g.finish()
+}
+
+/// Replay every scenario in [`SCENARIO_DIR`] against both tree types.
+fn scenario_benchmarks(c: &mut Criterion) {
+    let Ok(entries) = std::fs::read_dir(SCENARIO_DIR) else {
+        return;
+    };
+    let mut trees: HashMap<String, (Tree, TreeCount)> = HashMap::new();
+    for entry in entries {
+        let path = entry.expect("Valid directory entry").path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let name = path.file_stem().expect("Valid file name").to_string_lossy();
+        let data = std::fs::read_to_string(&path).expect("Valid file");
+        let scenario: Scenario = from_str(&data).expect("Valid scenario!");
+        let (tree, tree_count) = trees.entry(scenario.data.clone()).or_insert_with(|| {
+            let data = std::fs::read_to_string(&scenario.data).expect("Valid file");
+            let root: A11yNode = from_str(&data).expect("Valid JSON data!");
+            let mut tree = Tree::from_root_node(root.clone());
+            let mut tree_count = TreeCount::from_root_node(root);
+            tree.build_rolesets();
+            tree_count.build_rolesets();
+            (tree, tree_count)
+        });
+        {
+            let b = c.benchmark_group(format!("scenario/{name}/tree"));
+            scenario_bench(b, tree, &scenario.steps);
+        }
+        {
+            let b = c.benchmark_group(format!("scenario/{name}/count_tree"));
+            scenario_bench(b, tree_count, &scenario.steps);
+        }
+    }
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let real_data = std::fs::read_to_string(REAL_FN).expect("Valid file");
+    let synth_data = std::fs::read_to_string(SYNTH_FN).expect("Valid file");
+
+    let real_tree: A11yNode = from_str(&real_data).expect("Valid JSON data!");
+    let synth_tree: A11yNode = from_str(&synth_data).expect("Valid JSON data!");
+    // `from_root_node` no longer builds rolesets, so the `*_roleset` methods would only measure
+    // their fallback
+    let real_tree_plain = Tree::from_root_node_with(real_tree.clone(), TreeConfig::default());
+    let real_tree_all = Tree::from_root_node_with(real_tree.clone(), TreeConfig::ALL);
+    let real_tree_headings = Tree::from_root_node_with(real_tree.clone(), TreeConfig::default())
+        .project(Role::Heading.into());
+    let mut real_tree_count = TreeCount::from_root_node(real_tree);
+    real_tree_count.build_rolesets();
+    let synth_tree_plain = Tree::from_root_node_with(synth_tree.clone(), TreeConfig::default());
+    let mut synth_tree_count = TreeCount::from_root_node(synth_tree);
+    synth_tree_count.build_rolesets();
+
+    {
+        let b = c.benchmark_group("real/tree/parallel");
+        par_bench(b, &real_tree_plain, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree/sequential");
+        seq_bench(b, &real_tree_plain, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree/cached");
+        cached_bench(b, &real_tree_plain, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree_all_indexes/sequential");
+        seq_bench(b, &real_tree_all, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree_headings/sequential");
+        seq_bench(b, &real_tree_headings, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree_all_indexes/cached");
+        cached_bench(b, &real_tree_all, false);
+    }
+    {
+        let b = c.benchmark_group("real/tree_headings/cached");
+        cached_bench(b, &real_tree_headings, false);
+    }
+    {
+        let b = c.benchmark_group("real/count_tree/parallel");
+        par_bench(b, &real_tree_count, false);
+    }
+    {
+        let b = c.benchmark_group("real/count_tree/sequential");
+        seq_bench(b, &real_tree_count, false);
+    }
+    {
+        let b = c.benchmark_group("synth/tree/parallel");
+        par_bench(b, &synth_tree_plain, true);
+    }
+    {
+        let b = c.benchmark_group("synth/tree/sequential");
+        seq_bench(b, &synth_tree_plain, true);
+    }
+    {
+        let b = c.benchmark_group("synth/tree/cached");
+        cached_bench(b, &synth_tree_plain, true);
+    }
+    {
+        let b = c.benchmark_group("synth/count_tree/parallel");
+        par_bench(b, &synth_tree_count, true);
+    }
+    {
+        let b = c.benchmark_group("synth/count_tree/sequential");
+        seq_bench(b, &synth_tree_count, true);
+    }
+}
+
+/// Evenly spread nodes to append to during a burst of writes.
+fn burst_parents<T: TreeTraversal>(t: &T) -> Vec<indextree::NodeId> {
+    let step = (t.nodes() / BURST).max(1);
+    t.root()
+        .descendants(t.arena())
+        .step_by(step)
+        .take(BURST)
+        .collect()
+}
+
+/// A burst of writes, each appending a small subtree.
+fn burst<T: TreeTraversal>(t: &mut T, append: fn(&mut T, indextree::NodeId, A11yNode)) {
+    let node: A11yNode =
+        from_str(r#"{"role": "Section", "children": [{"role": "Heading", "children": []}]}"#)
+            .expect("Valid JSON data!");
+    for parent in burst_parents(t) {
+        append(t, parent, node.clone());
+    }
+}
+
+/// Compare eager roleset maintenance ([`Tree`]) with batched refreshes ([`LazyTree`]): write
+/// throughput, and how much slower queries are until the next refresh.
+fn write_benchmarks(c: &mut Criterion) {
+    let real_data = std::fs::read_to_string(REAL_FN).expect("Valid file");
+    let real_tree: A11yNode = from_str(&real_data).expect("Valid JSON data!");
+    let eager = || {
+        let mut t = Tree::from_root_node(real_tree.clone());
+        t.build_rolesets();
+        t
+    };
+    let lazy = || {
+        let mut t = LazyTree::from_root_node(real_tree.clone());
+        t.build_rolesets();
+        t
+    };
+    let append_eager: fn(&mut Tree, _, _) = |t, parent, node| {
+        t.append_subtree(parent, node);
+    };
+    let append_lazy: fn(&mut LazyTree, _, _) = |t, parent, node| {
+        t.append_subtree(parent, node);
+    };
+
+    let mut g = c.benchmark_group("real/writes");
+    g.throughput(Throughput::Elements(BURST as u64));
+    g.bench_function("eager", |b| {
+        b.iter_batched(
+            eager,
+            |mut t| {
+                burst(&mut t, append_eager);
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.bench_function("lazy", |b| {
+        b.iter_batched(
+            lazy,
+            |mut t| {
+                burst(&mut t, append_lazy);
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.bench_function("lazy_refresh", |b| {
+        b.iter_batched(
+            lazy,
+            |mut t| {
+                burst(&mut t, append_lazy);
+                t.refresh();
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.finish();
+
+    // a role which is absent before the burst, so stale subtrees cannot be pruned
+    let mut stale = lazy();
+    let role = Role::Footnote;
+    burst(&mut stale, append_lazy);
+    let mut fresh = lazy();
+    burst(&mut fresh, append_lazy);
+    fresh.refresh();
+    let mut eager = eager();
+    burst(&mut eager, append_eager);
+    let mut g = c.benchmark_group("real/writes/query_lag");
+    g.throughput(Throughput::Elements(1_u64));
+    g.bench_function("eager", |b| {
+        b.iter(|| black_box(eager.how_many_roleset(role)))
+    });
+    g.bench_function("lazy_stale", |b| {
+        b.iter(|| black_box(stale.how_many_roleset(role)))
+    });
+    g.bench_function("lazy_fresh", |b| {
+        b.iter(|| black_box(fresh.how_many_roleset(role)))
+    });
+    g.finish();
+}
+
+fn count_nodes(node: &A11yNode) -> usize {
+    1 + node.children().iter().map(count_nodes).sum::<usize>()
+}
+
+/// A desktop with [`APPS`] copies of the real data's largest application; only the last one
+/// contains a [`Role::Footnote`].
+fn desktop(real_tree: &A11yNode) -> A11yNode {
+    let app = real_tree
+        .children()
+        .iter()
+        .max_by_key(|app| count_nodes(app))
+        .expect("Real data has applications");
+    let app = serde_json::to_value(app).expect("Serializable");
+    let mut last = app.clone();
+    last["children"]
+        .as_array_mut()
+        .expect("Children are an array")
+        .push(serde_json::json!({"role": "Footnote", "children": []}));
+    let mut apps = vec![app; APPS - 1];
+    apps.push(last);
+    serde_json::from_value(serde_json::json!({"role": real_tree.role(), "children": apps}))
+        .expect("Valid JSON data!")
+}
+
+/// Compare one index over a whole multi-app desktop ([`Tree`]) with one index per application
+/// ([`ShardedTree`]): queries, and rebuilding after one application changes.
+fn shard_benchmarks(c: &mut Criterion) {
+    let real_data = std::fs::read_to_string(REAL_FN).expect("Valid file");
+    let real_tree: A11yNode = from_str(&real_data).expect("Valid JSON data!");
+    let desktop = desktop(&real_tree);
+    let config = TreeConfig::default();
+    let whole = Tree::from_root_node_with(desktop.clone(), config);
+    let sharded = ShardedTree::from_root_node_with(desktop.clone(), config);
+    let changed = desktop.children()[0].clone();
+
+    let mut g = c.benchmark_group("desktop/monolithic");
+    g.throughput(Throughput::Elements(1_u64));
+    g.bench_function("find_first_rare", |b| {
+        b.iter(|| black_box(whole.find_first_cached(Role::Footnote)))
+    });
+    g.bench_function("how_many_common", |b| {
+        b.iter(|| black_box(whole.how_many_roleset(Role::Link)))
+    });
+    g.bench_function("rebuild_all", |b| {
+        b.iter_batched(
+            || desktop.clone(),
+            |desktop| Tree::from_root_node_with(desktop, config),
+            BatchSize::LargeInput,
+        )
+    });
+    g.bench_function("rebuild_one_app", |b| {
+        b.iter_batched(
+            || Tree::from_root_node_with(desktop.clone(), config),
+            |mut t| {
+                let app = t.root().children(t.arena()).next().expect("Has apps");
+                t.remove_subtree(app);
+                t.append_subtree(t.root(), changed.clone());
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.finish();
+
+    let mut g = c.benchmark_group("desktop/sharded");
+    g.throughput(Throughput::Elements(1_u64));
+    g.bench_function("find_first_rare", |b| {
+        b.iter(|| black_box(sharded.find_first(Role::Footnote)))
+    });
+    g.bench_function("how_many_common", |b| {
+        b.iter(|| black_box(sharded.how_many(Role::Link)))
+    });
+    g.bench_function("rebuild_all", |b| {
+        b.iter_batched(
+            || desktop.clone(),
+            |desktop| ShardedTree::from_root_node_with(desktop, config),
+            BatchSize::LargeInput,
+        )
+    });
+    g.bench_function("rebuild_one_app", |b| {
+        b.iter_batched(
+            || ShardedTree::from_root_node_with(desktop.clone(), config),
+            |mut t| {
+                t.replace_shard(0, changed.clone());
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    g.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmarks,
+    scenario_benchmarks,
+    write_benchmarks,
+    shard_benchmarks
+);
+criterion_main!(benches);
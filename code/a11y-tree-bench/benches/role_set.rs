@@ -0,0 +1,163 @@
+//! Micro-benchmarks for [`RoleSet`] operations, which are the innermost loop of every traversal.
+//!
+//! Each fast path is compared with the generic implementation it replaced.
+
+use a11y_tree_core::order::{OrderedRoleSet, RoleOrder};
+use a11y_tree_core::{A11yNode, HasRole, NodeIdExt, RoleSet, Tree, TreeTraversal};
+use atspi_common::Role;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::from_str;
+
+const REAL_FN: &str = "../../data/single-page-html-spec.json";
+
+/// Iterate a [`RoleSet`] by testing every bit.
+fn generic_role_iter(rs: RoleSet) -> impl Iterator<Item = Role> {
+    (0..u128::BITS + u8::BITS)
+        .filter_map(|i| Role::try_from(i).ok())
+        .filter(move |role| rs.contains((*role).into()))
+}
+
+/// Count a role by visiting every node in subtrees which contain it.
+fn generic_how_many<T: TreeTraversal>(t: &T, role: Role) -> usize {
+    let arena = t.arena();
+    t.root()
+        .descendants_role(arena, role.into())
+        .filter(|id| arena[*id].get().role() == role)
+        .count()
+}
+
+fn role_set_benchmarks(c: &mut Criterion) {
+    let real_data = std::fs::read_to_string(REAL_FN).expect("Valid file");
+    let real_tree: A11yNode = from_str(&real_data).expect("Valid JSON data!");
+    let mut tree = Tree::from_root_node(real_tree);
+    tree.build_rolesets();
+    let sets = [
+        ("all", RoleSet::ALL),
+        ("real", tree.unique_roles_roleset()),
+        ("single", Role::Heading.into()),
+    ];
+
+    let mut g = c.benchmark_group("role_set/len");
+    for (name, rs) in sets {
+        g.bench_function(format!("{name}/popcount"), |b| {
+            b.iter(|| black_box(black_box(rs).len()))
+        });
+        g.bench_function(format!("{name}/generic"), |b| {
+            b.iter(|| black_box(generic_role_iter(black_box(rs)).count()))
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("role_set/role_iter");
+    for (name, rs) in sets {
+        g.bench_function(format!("{name}/trailing_zeros"), |b| {
+            b.iter(|| {
+                black_box(rs).role_iter().for_each(|r| {
+                    black_box(r);
+                })
+            })
+        });
+        g.bench_function(format!("{name}/generic"), |b| {
+            b.iter(|| {
+                generic_role_iter(black_box(rs)).for_each(|r| {
+                    black_box(r);
+                })
+            })
+        });
+    }
+    g.finish();
+
+    // a long list is the best case: each item's subtree has only one role
+    let list: A11yNode = from_str(&format!(
+        r#"{{"role": "List", "children": [{}]}}"#,
+        vec![r#"{"role": "ListItem", "children": [{"role": "ListItem", "children": []}]}"#; 5_000]
+            .join(",")
+    ))
+    .expect("Valid JSON data!");
+    let mut list = Tree::from_root_node(list);
+    list.build_rolesets();
+    let common = tree
+        .role_counts_in(tree.root())
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .expect("Tree is not empty")
+        .0;
+    let cases = [
+        ("real/common", &tree, common),
+        ("real/heading", &tree, Role::Heading),
+        ("list/list_item", &list, Role::ListItem),
+    ];
+    let mut g = c.benchmark_group("role_set/how_many_roleset");
+    for (name, t, role) in cases {
+        assert_eq!(t.how_many_roleset(role), generic_how_many(t, role));
+        g.bench_function(format!("{name}/uniform_subtrees"), |b| {
+            b.iter(|| black_box(t.how_many_roleset(black_box(role))))
+        });
+        g.bench_function(format!("{name}/generic"), |b| {
+            b.iter(|| black_box(generic_how_many(t, black_box(role))))
+        });
+    }
+    g.finish();
+
+    bit_order_benchmarks(c, &tree);
+}
+
+/// The pruning check (does a subtree contain a role?) over every node's roleset, with the fixed
+/// bit order, and with orders which put common roles in the first machine word.
+fn bit_order_benchmarks(c: &mut Criterion, tree: &Tree) {
+    let counts = tree.role_counts_in(tree.root());
+    let rarest = counts
+        .iter()
+        .min_by_key(|(_, count)| *count)
+        .expect("Tree is not empty")
+        .0;
+    let common = counts
+        .iter()
+        .max_by_key(|(_, count)| *count)
+        .expect("Tree is not empty")
+        .0;
+    let rolesets = tree
+        .root()
+        .descendants(tree.arena())
+        .map(|id| tree.arena()[id].get().roleset())
+        .collect::<Vec<_>>();
+    let orders = [
+        ("identity_order", RoleOrder::default()),
+        ("frequency_order", tree.role_order()),
+    ];
+    let mut g = c.benchmark_group("role_set/bit_order");
+    for (name, role) in [
+        ("common", common),
+        ("heading", Role::Heading),
+        ("rarest", rarest),
+    ] {
+        let target = RoleSet::from(role);
+        g.bench_function(format!("{name}/role_set"), |b| {
+            b.iter(|| {
+                black_box(&rolesets)
+                    .iter()
+                    .filter(|rs| rs.contains(black_box(target)))
+                    .count()
+            })
+        });
+        for (order_name, order) in &orders {
+            let ordered = rolesets
+                .iter()
+                .map(|rs| order.set(*rs))
+                .collect::<Vec<OrderedRoleSet>>();
+            let target = order.set(target);
+            g.bench_function(format!("{name}/{order_name}"), |b| {
+                b.iter(|| {
+                    black_box(&ordered)
+                        .iter()
+                        .filter(|rs| rs.contains(black_box(target)))
+                        .count()
+                })
+            });
+        }
+    }
+    g.finish();
+}
+
+criterion_group!(benches, role_set_benchmarks);
+criterion_main!(benches);
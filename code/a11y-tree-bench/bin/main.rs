@@ -0,0 +1,526 @@
+use a11y_tree_core::{
+    canonical::ChildOrder,
+    graph::{FlatSnapshot, OnMalformed},
+    limits::{self, Limits},
+    parse_role,
+    split::{write_split, MANIFEST},
+    trace::{timeline, timeline_csv, MutationEvent},
+    A11yNode, Scenario, Step, Tree, TreeCount, TreeTraversal,
+};
+use atspi_common::Role;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+mod matrix;
+use matrix::Matrix;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const USAGE: &str = "Usage:
+    a11y-tree-bench [stats] [--json] FILE
+    a11y-tree-bench query [--watch] FILE ROLE...
+    a11y-tree-bench repl [--record SCENARIO] FILE
+    a11y-tree-bench matrix [--csv] FILE
+    a11y-tree-bench split FILE DIR
+    a11y-tree-bench timeline [--csv] TRACE
+    a11y-tree-bench diff [--sorted] FILE OTHER
+    a11y-tree-bench import [--repair] FILE
+
+Snapshots are refused if they have more than --max-nodes N nodes, need more than --max-bytes N
+bytes, or are more than --max-depth N nodes deep (see `limits::Limits`); by default there is no
+limit.";
+
+/// How long to wait for more file system events before re-running a watched query.
+/// Editors often write a file in several steps.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// The subcommand to run.
+enum Command {
+    /// Print statistics about the tree, and timings of various traversal methods.
+    Stats,
+    /// Print how many of each role are in the tree, and where the first of each is.
+    Query,
+    /// Read queries from stdin, and print their results and timings.
+    Repl,
+    /// Print a table of timings for each role and method.
+    Matrix,
+    /// Write each application in a snapshot to its own file, with a manifest.
+    Split,
+    /// Print a per-second summary of a trace of mutations.
+    Timeline,
+    /// Print the first difference between two snapshots.
+    Diff,
+    /// Convert a flat snapshot to a tree, checking for cycles and duplicate edges.
+    Import,
+}
+
+struct Args {
+    command: Command,
+    /// Output machine-readable JSON instead of text.
+    json: bool,
+    /// Output CSV instead of Markdown.
+    csv: bool,
+    /// Re-run the command whenever the file changes.
+    watch: bool,
+    file_name: String,
+    /// Roles to query for.
+    roles: Vec<Role>,
+    /// Where to record the queries run in the REPL, as a [`Scenario`].
+    record: Option<String>,
+    /// Where to write a split snapshot.
+    out_dir: Option<String>,
+    /// The snapshot to compare with.
+    other: Option<String>,
+    /// How to order children when comparing snapshots.
+    order: ChildOrder,
+    /// What to do with edges which would not make a tree when importing.
+    on_malformed: OnMalformed,
+    /// The largest snapshot to read.
+    limits: Limits,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut command = Command::Stats;
+        let mut json = false;
+        let mut csv = false;
+        let mut watch = false;
+        let mut file_name = None;
+        let mut roles = Vec::new();
+        let mut record = None;
+        let mut out_dir = None;
+        let mut other = None;
+        let mut order = ChildOrder::AsIs;
+        let mut on_malformed = OnMalformed::Reject;
+        let mut limits = Limits::NONE;
+        let mut args = env::args().skip(1).enumerate();
+        while let Some((i, arg)) = args.next() {
+            match arg.as_str() {
+                "stats" if i == 0 => command = Command::Stats,
+                "query" if i == 0 => command = Command::Query,
+                "repl" if i == 0 => command = Command::Repl,
+                "matrix" if i == 0 => command = Command::Matrix,
+                "split" if i == 0 => command = Command::Split,
+                "timeline" if i == 0 => command = Command::Timeline,
+                "diff" if i == 0 => command = Command::Diff,
+                "import" if i == 0 => command = Command::Import,
+                "--sorted" => order = ChildOrder::Sorted,
+                "--repair" => on_malformed = OnMalformed::Repair,
+                "--json" => json = true,
+                "--csv" => csv = true,
+                "--watch" => watch = true,
+                "--record" => record = Some(args.next().ok_or(USAGE)?.1),
+                "--max-nodes" | "--max-bytes" | "--max-depth" => {
+                    let value = args.next().ok_or(USAGE)?.1;
+                    let max = value
+                        .parse()
+                        .map_err(|e| format!("Invalid {arg} {value}: {e}"))?;
+                    match arg.as_str() {
+                        "--max-nodes" => limits.max_nodes = max,
+                        "--max-bytes" => limits.max_bytes = max,
+                        _ => limits.max_depth = max,
+                    }
+                }
+                _ if file_name.is_none() => file_name = Some(arg),
+                _ if matches!(command, Command::Split) && out_dir.is_none() => out_dir = Some(arg),
+                _ if matches!(command, Command::Diff) && other.is_none() => other = Some(arg),
+                _ if matches!(command, Command::Query) => {
+                    roles.push(parse_role(&arg).ok_or(format!("Unknown role: {arg}"))?);
+                }
+                _ => return Err(format!("Unexpected argument: {arg}\n{USAGE}").into()),
+            }
+        }
+        if matches!(command, Command::Query) && roles.is_empty() {
+            return Err(format!("Missing roles to query\n{USAGE}").into());
+        }
+        if matches!(command, Command::Split) && out_dir.is_none() {
+            return Err(format!("Missing output directory\n{USAGE}").into());
+        }
+        if matches!(command, Command::Diff) && other.is_none() {
+            return Err(format!("Missing snapshot to compare with\n{USAGE}").into());
+        }
+        Ok(Args {
+            command,
+            json,
+            csv,
+            watch,
+            file_name: file_name.ok_or(USAGE)?,
+            roles,
+            record,
+            out_dir,
+            other,
+            order,
+            on_malformed,
+            limits,
+        })
+    }
+}
+
+/// Machine-readable statistics about a tree.
+#[derive(Serialize)]
+struct Stats {
+    file: String,
+    nodes: usize,
+    leafs: usize,
+    max_depth: usize,
+    unique_roles: usize,
+    /// Number of nodes with each role, in ascending numeric order of the role.
+    roles: Vec<RoleStats>,
+    timings: Timings,
+}
+
+#[derive(Serialize)]
+struct RoleStats {
+    role: Role,
+    count: usize,
+}
+
+/// Timings, in seconds.
+#[derive(Serialize)]
+struct Timings {
+    read_secs: f64,
+    parse_secs: f64,
+    roleset_index_secs: f64,
+    count_index_secs: f64,
+}
+
+/// A tree loaded from a file, with timings of each step.
+struct Loaded {
+    tree: Tree,
+    tree_count: TreeCount,
+    read: Duration,
+    parse: Duration,
+    roleset_index: Duration,
+    count_index: Duration,
+}
+
+/// Read a snapshot, refusing it if it exceeds `limits`.
+fn read_snapshot(file_name: &str, limits: Limits) -> Result<A11yNode> {
+    Ok(limits::from_json_str(
+        &fs::read_to_string(file_name)?,
+        limits,
+    )?)
+}
+
+fn load(file_name: &str, limits: Limits) -> Result<Loaded> {
+    let read_start = Instant::now();
+    let data = fs::read_to_string(file_name)?;
+    let read = read_start.elapsed();
+    let json_start = Instant::now();
+    let a11y_node = limits::from_json_str(&data, limits)?;
+    let parse = json_start.elapsed();
+    let mut tree = Tree::from_root_node(a11y_node.clone());
+    let mut tree_count = TreeCount::from_root_node(a11y_node);
+    let start = Instant::now();
+    tree.build_rolesets();
+    let roleset_index = start.elapsed();
+    let startcount = Instant::now();
+    tree_count.build_rolesets();
+    let count_index = startcount.elapsed();
+    Ok(Loaded {
+        tree,
+        tree_count,
+        read,
+        parse,
+        roleset_index,
+        count_index,
+    })
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+    if args.watch {
+        watch(&args)
+    } else {
+        run(&args)
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    match args.command {
+        Command::Stats if args.json => stats_json(&args.file_name, args.limits),
+        Command::Stats => stats(&args.file_name, args.limits),
+        Command::Query => query(&args.file_name, &args.roles, args.limits),
+        Command::Repl => repl(&args.file_name, args.record.as_deref(), args.limits),
+        Command::Matrix => {
+            let Loaded {
+                mut tree,
+                tree_count,
+                ..
+            } = load(&args.file_name, args.limits)?;
+            tree.set_skip_lists(tree.unique_roles_roleset());
+            let matrix = Matrix::measure(&tree, &tree_count);
+            if args.csv {
+                print!("{}", matrix.to_csv());
+            } else {
+                print!("{}", matrix.to_markdown());
+            }
+            Ok(())
+        }
+        Command::Split => split(
+            &args.file_name,
+            args.out_dir.as_deref().expect("Checked when parsing"),
+            args.limits,
+        ),
+        Command::Diff => diff(
+            &args.file_name,
+            args.other.as_deref().expect("Checked when parsing"),
+            args.order,
+            args.limits,
+        ),
+        Command::Import => import(&args.file_name, args.on_malformed, args.limits),
+        Command::Timeline => {
+            let events: Vec<MutationEvent> =
+                serde_json::from_str(&fs::read_to_string(&args.file_name)?)?;
+            let timeline = timeline(&events);
+            if args.csv {
+                print!("{}", timeline_csv(&timeline));
+            } else {
+                println!("{}", serde_json::to_string_pretty(&timeline)?);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn diff(file_name: &str, other: &str, order: ChildOrder, limits: Limits) -> Result<()> {
+    let mut a = read_snapshot(file_name, limits)?;
+    let mut b = read_snapshot(other, limits)?;
+    if order == ChildOrder::Sorted {
+        a.canonicalize();
+        b.canonicalize();
+    }
+    let Some(path) = a.first_difference(&b, ChildOrder::AsIs) else {
+        println!(
+            "Identical (hash {:016x})",
+            a.structural_hash(ChildOrder::AsIs)
+        );
+        return Ok(());
+    };
+    let at = |root: &A11yNode| {
+        let node = path.iter().fold(root, |node, i| &node.children()[*i]);
+        format!(
+            "{} {:?} with {} children",
+            node.role(),
+            node.name().unwrap_or_default(),
+            node.children().len()
+        )
+    };
+    println!("First difference at path {path:?}:");
+    println!("{file_name}: {}", at(&a));
+    println!("{other}: {}", at(&b));
+    Ok(())
+}
+
+/// Print a flat snapshot as a tree, reporting any edges dropped to make it one.
+fn import(file_name: &str, on_malformed: OnMalformed, limits: Limits) -> Result<()> {
+    let flat: FlatSnapshot = serde_json::from_str(&fs::read_to_string(file_name)?)?;
+    let import = flat.to_tree(on_malformed, limits)?;
+    for repaired in &import.repaired {
+        eprintln!("Dropped edge: {repaired}");
+    }
+    println!("{}", serde_json::to_string(&import.root)?);
+    Ok(())
+}
+
+fn split(file_name: &str, out_dir: &str, limits: Limits) -> Result<()> {
+    let root = read_snapshot(file_name, limits)?;
+    let manifest = write_split(root, Path::new(out_dir))?;
+    for app in &manifest.apps {
+        println!("{}: {} nodes ({})", app.name, app.nodes, app.file);
+    }
+    println!(
+        "Wrote {} applications and {MANIFEST} to {out_dir}",
+        manifest.apps.len()
+    );
+    Ok(())
+}
+
+/// Run the command once, then again every time the file changes.
+fn watch(args: &Args) -> Result<()> {
+    let path = fs::canonicalize(&args.file_name)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    // watch the directory, not the file: many editors replace the file instead of writing to it
+    watcher.watch(
+        path.parent().unwrap_or(Path::new("/")),
+        RecursiveMode::NonRecursive,
+    )?;
+    loop {
+        if let Err(e) = run(args) {
+            eprintln!("Error: {e}");
+        }
+        println!("Watching {} for changes...", path.display());
+        loop {
+            // only a disconnected channel ends watch mode; notify errors are often transient,
+            // e.g., when an editor's rename races the watch
+            let event = match rx.recv()? {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Watch error: {e}");
+                    continue;
+                }
+            };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.contains(&path)
+            {
+                break;
+            }
+        }
+        // drain the rest of this burst of events
+        while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+    }
+}
+
+fn query(file_name: &str, roles: &[Role], limits: Limits) -> Result<()> {
+    let a11y_node = read_snapshot(file_name, limits)?;
+    let mut tree = Tree::from_root_node(a11y_node);
+    tree.build_rolesets();
+    for role in roles {
+        let start = Instant::now();
+        let first = tree.find_first_roleset(*role);
+        let end = Instant::now();
+        let many = tree.how_many_roleset(*role);
+        println!("{role}: {many}");
+        match first.and_then(|node| tree.node_id(node)) {
+            Some(id) => println!("\tFirst at path {:?} ({:?})", tree.path(id), end - start),
+            None => println!("\tNot found ({:?})", end - start),
+        }
+    }
+    Ok(())
+}
+
+/// Run queries (see [`Step`]) read from stdin, one per line, until EOF or `quit`.
+/// If `record` is given, all successful queries are saved there as a [`Scenario`] on exit.
+fn repl(file_name: &str, record: Option<&str>, limits: Limits) -> Result<()> {
+    let Loaded { tree, .. } = load(file_name, limits)?;
+    let mut scenario = Scenario {
+        data: file_name.to_string(),
+        steps: Vec::new(),
+    };
+    let mut stdout = io::stdout();
+    print!("> ");
+    stdout.flush()?;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            line => match line.parse::<Step>() {
+                Ok(step) => {
+                    let start = Instant::now();
+                    let (output, plan) = step.run_planned(&tree);
+                    let end = Instant::now();
+                    println!("{output} ({:?}, {plan})", end - start);
+                    scenario.steps.push(step);
+                }
+                Err(e) => eprintln!("Error: {e}"),
+            },
+        }
+        print!("> ");
+        stdout.flush()?;
+    }
+    println!();
+    if let Some(record) = record {
+        fs::write(record, serde_json::to_string_pretty(&scenario)?)?;
+        println!("Recorded {} steps to {record}", scenario.steps.len());
+    }
+    Ok(())
+}
+
+fn stats_json(file_name: &str, limits: Limits) -> Result<()> {
+    let loaded = load(file_name, limits)?;
+    let tree = &loaded.tree;
+    let roles = tree
+        .role_counts_in(tree.root())
+        .into_iter()
+        .map(|(role, count)| RoleStats { role, count })
+        .collect::<Vec<_>>();
+    let stats = Stats {
+        file: file_name.to_string(),
+        nodes: tree.nodes(),
+        leafs: tree.iter_leafs().count(),
+        max_depth: tree.max_depth(),
+        unique_roles: roles.len(),
+        roles,
+        timings: Timings {
+            read_secs: loaded.read.as_secs_f64(),
+            parse_secs: loaded.parse.as_secs_f64(),
+            roleset_index_secs: loaded.roleset_index.as_secs_f64(),
+            count_index_secs: loaded.count_index.as_secs_f64(),
+        },
+    };
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    Ok(())
+}
+
+fn stats(file_name: &str, limits: Limits) -> Result<()> {
+    let Loaded {
+        tree,
+        tree_count,
+        read,
+        parse,
+        roleset_index,
+        count_index,
+    } = load(file_name, limits)?;
+    println!("Took {read:?} to read file");
+    println!("Took {parse:?} to parse JSON");
+    println!("Took {roleset_index:?} to build bitset roleset index");
+    println!("Took {count_index:?} to build count roleset index");
+    println!("Total nodes: {:?}", tree.nodes());
+    println!("Tree leafs: {:?}", tree.iter_leafs().count());
+    for role in tree.unique_roles().role_iter() {
+        {
+            let many = tree.how_many(role);
+            let start = Instant::now();
+            let first = tree.find_first(role);
+            let end = Instant::now();
+            let startset = Instant::now();
+            let firstset = tree.find_first_stack(role);
+            let endset = Instant::now();
+            let startfast = Instant::now();
+            let firstfast = tree.find_first_roleset(role);
+            let endfast = Instant::now();
+            assert_eq!(first, firstset);
+            assert_eq!(firstset, firstfast);
+            println!("\t{}: {}", role, many);
+            println!("\t\tTime for standard traversal: {:?}", end - start);
+            println!("\t\tTime for roleset traversal: {:?}", endset - startset);
+            println!(
+                "\t\tTime for indextree extention: {:?}",
+                endfast - startfast
+            );
+        }
+        {
+            let many = tree_count.how_many(role);
+            let start = Instant::now();
+            let first = tree_count.find_first(role);
+            let end = Instant::now();
+            let startset = Instant::now();
+            let firstset = tree_count.find_first_stack(role);
+            let endset = Instant::now();
+            let startfast = Instant::now();
+            let firstfast = tree_count.find_first_roleset(role);
+            let endfast = Instant::now();
+            assert_eq!(first, firstset);
+            assert_eq!(firstset, firstfast);
+            println!("\t{}: {}", role, many);
+            println!("\t\tTime for standard traversal: {:?}", end - start);
+            println!("\t\tTime for roleset traversal: {:?}", endset - startset);
+            println!(
+                "\t\tTime for indextree extention: {:?}",
+                endfast - startfast
+            );
+        }
+    }
+    println!("Max depth: {}", tree.max_depth());
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Per-role benchmark matrix: times each method for every role present in a tree, for every
+//! backend.
+//!
+//! Aggregate benchmarks pick random roles, which hides that pruning helps rare roles a lot and
+//! common roles barely at all.
+
+use a11y_tree_core::{Tree, TreeCount, TreeTraversal};
+use atspi_common::Role;
+use std::fmt::Write;
+use std::hint::black_box;
+use std::iter::successors;
+use std::time::{Duration, Instant};
+
+/// How many times each method is run per role; the median is reported.
+const RUNS: usize = 15;
+
+/// A method under test, by name.
+type Method<T> = (&'static str, fn(&T, Role));
+/// A `find_next` method.
+type Next<'a, T> =
+    fn(&'a T, indextree::NodeId, Role) -> Option<&'a indextree::Node<<T as TreeTraversal>::Node>>;
+
+/// Visit every node with `role`, as a user repeatedly pressing a "next heading" key would.
+fn visit_all<'a, T: TreeTraversal>(t: &'a T, role: Role, next: Next<'a, T>) {
+    successors(Some(t.root()), |from| {
+        next(t, *from, role).and_then(|node| t.node_id(node))
+    })
+    .for_each(|id| {
+        black_box(id);
+    });
+}
+
+/// The `find_next` methods are timed visiting every node with the role, starting from the root.
+fn methods<T: TreeTraversal>() -> [Method<T>; 12] {
+    [
+        ("find_first", |t, r| {
+            black_box(t.find_first(r));
+        }),
+        ("find_first_roleset", |t, r| {
+            black_box(t.find_first_roleset(r));
+        }),
+        ("find_first_stack", |t, r| {
+            black_box(t.find_first_stack(r));
+        }),
+        ("find_first_cached", |t, r| {
+            black_box(t.find_first_cached(r));
+        }),
+        ("par_find_first", |t, r| {
+            black_box(t.par_find_first(r));
+        }),
+        ("par_find_first_roleset", |t, r| {
+            black_box(t.par_find_first_roleset(r));
+        }),
+        ("find_next", |t, r| visit_all(t, r, T::find_next)),
+        ("find_next_cached", |t, r| {
+            visit_all(t, r, T::find_next_cached)
+        }),
+        ("how_many", |t, r| {
+            black_box(t.how_many(r));
+        }),
+        ("how_many_roleset", |t, r| {
+            black_box(t.how_many_roleset(r));
+        }),
+        ("par_how_many", |t, r| {
+            black_box(t.par_how_many(r));
+        }),
+        ("par_how_many_roleset", |t, r| {
+            black_box(t.par_how_many_roleset(r));
+        }),
+    ]
+}
+
+fn median_time<T: TreeTraversal>(tree: &T, role: Role, method: fn(&T, Role)) -> Duration {
+    let mut times = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            method(tree, role);
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+    times.sort_unstable();
+    times[RUNS / 2]
+}
+
+/// A table of timings: one row per role, one column per backend and method.
+pub struct Matrix {
+    columns: Vec<String>,
+    /// Role, number of nodes with that role, and a timing for each column.
+    rows: Vec<(Role, usize, Vec<Duration>)>,
+    /// Memory used by [`Tree`]'s skip lists once every role has been queried.
+    skip_list_bytes: usize,
+    /// How many roles [`Tree`] keeps skip lists for.
+    skip_list_roles: usize,
+}
+
+impl Matrix {
+    /// Time every method for every role in the tree.
+    /// Rows are sorted from the rarest to the most common role.
+    ///
+    /// Set skip lists on `tree` beforehand (see [`Tree::set_skip_lists`]) to compare their
+    /// latency with their memory use.
+    pub fn measure(tree: &Tree, tree_count: &TreeCount) -> Self {
+        let backend_columns = |backend: &str, names: Vec<&str>| {
+            names
+                .into_iter()
+                .map(|name| format!("{backend}/{name}"))
+                .collect::<Vec<_>>()
+        };
+        let mut columns = backend_columns("tree", methods::<Tree>().map(|m| m.0).to_vec());
+        columns.extend(backend_columns(
+            "count_tree",
+            methods::<TreeCount>().map(|m| m.0).to_vec(),
+        ));
+        let mut rows = tree
+            .role_counts_in(tree.root())
+            .into_iter()
+            .map(|(role, count)| {
+                let times = methods::<Tree>()
+                    .iter()
+                    .map(|(_, method)| median_time(tree, role, *method))
+                    .chain(
+                        methods::<TreeCount>()
+                            .iter()
+                            .map(|(_, method)| median_time(tree_count, role, *method)),
+                    )
+                    .collect();
+                (role, count, times)
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|(_, count, _)| *count);
+        Matrix {
+            columns,
+            rows,
+            skip_list_bytes: tree.skip_list_bytes(),
+            skip_list_roles: tree.skip_list_roles().len(),
+        }
+    }
+
+    /// Format as CSV, with times in nanoseconds.
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("role,count,{}\n", self.columns.join(","));
+        for (role, count, times) in &self.rows {
+            let _ = write!(out, "{role},{count}");
+            for time in times {
+                let _ = write!(out, ",{}", time.as_nanos());
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Format as a Markdown table.
+    /// The fastest method in each row is in bold.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("| role | count | {} |\n", self.columns.join(" | "));
+        out.push_str(&"| --- ".repeat(self.columns.len() + 2));
+        out.push_str("|\n");
+        for (role, count, times) in &self.rows {
+            let fastest = times.iter().min();
+            let _ = write!(out, "| {role} | {count} |");
+            for time in times {
+                if Some(time) == fastest {
+                    let _ = write!(out, " **{time:?}** |");
+                } else {
+                    let _ = write!(out, " {time:?} |");
+                }
+            }
+            out.push('\n');
+        }
+        if self.skip_list_roles > 0 {
+            let _ = writeln!(
+                out,
+                "\nSkip lists: {} KiB for {} roles ({} KiB per role).",
+                self.skip_list_bytes / 1024,
+                self.skip_list_roles,
+                self.skip_list_bytes / self.skip_list_roles / 1024,
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,237 @@
+//! Choosing which indexes to build within a memory budget.
+//!
+//! Every index in a [`TreeConfig`] trades memory for speed.
+//! On a large tree, a low-end device may not be able to afford all of them, so
+//! [`TreeConfig::within_budget`] keeps the most useful indexes which fit, using a
+//! [`UsageProfile`] to decide which roles are worth skip lists.
+
+use crate::{FirstOccurrence, RoleSet, RoleSetVecCount, Step, TreeConfig};
+use atspi_common::Role;
+use indextree::NodeId;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// How often each role is queried, e.g., by a user's structural navigation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageProfile(Vec<(Role, usize)>);
+
+impl UsageProfile {
+    /// Record a query for `role`.
+    pub fn record(&mut self, role: Role) {
+        match self.0.iter_mut().find(|pair| pair.0 == role) {
+            Some(pair) => pair.1 += 1,
+            None => self.0.push((role, 1)),
+        }
+    }
+    /// Build a profile from the queries in a recorded [`crate::Scenario`].
+    #[must_use]
+    pub fn from_steps(steps: &[Step]) -> Self {
+        let mut profile = UsageProfile::default();
+        for role in steps.iter().filter_map(|step| step.role()) {
+            profile.record(role);
+        }
+        profile
+    }
+    /// Returns the number of queries for `role`.
+    #[must_use]
+    pub fn count(&self, role: Role) -> usize {
+        self.0
+            .iter()
+            .find(|pair| pair.0 == role)
+            .map_or(0, |pair| pair.1)
+    }
+    /// Returns the roles in `roles`, from the most to the least queried.
+    /// Ties (including roles which were never queried) are in numeric order.
+    #[must_use]
+    pub fn hottest(&self, roles: RoleSet) -> Vec<Role> {
+        let mut hottest = roles.role_iter().collect::<Vec<_>>();
+        hottest.sort_by_key(|role| std::cmp::Reverse(self.count(*role)));
+        hottest
+    }
+}
+
+/// An optional index, as selected by a [`TreeConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Index {
+    /// [`TreeConfig::rolesets`]
+    Rolesets,
+    /// [`TreeConfig::counts`]
+    Counts,
+    /// [`TreeConfig::positions`]
+    Positions,
+    /// One role of [`TreeConfig::skip_lists`].
+    SkipList(Role),
+    /// [`TreeConfig::ancestors`]
+    Ancestors,
+}
+
+impl Index {
+    /// Returns the estimated memory used by this index on a tree of `nodes` nodes, in bytes.
+    ///
+    /// Rolesets are stored in every node whether built or not, so they only cost the table of
+    /// first occurrences.
+    /// Counts are estimated assuming each node's counts use their smallest allocation (four
+    /// roles); nodes with many distinct descendant roles use more.
+    #[must_use]
+    pub fn bytes(self, nodes: usize) -> usize {
+        match self {
+            Index::Rolesets => {
+                size_of::<FirstOccurrence>() + RoleSet::ALL.len() * size_of::<Option<NodeId>>()
+            }
+            Index::Counts => {
+                nodes * (size_of::<RoleSetVecCount>() + 4 * size_of::<(Role, usize)>())
+            }
+            Index::Positions | Index::Ancestors => nodes * size_of::<usize>(),
+            Index::SkipList(_) => nodes * size_of::<Option<NodeId>>(),
+        }
+    }
+}
+
+impl Display for Index {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Index::Rolesets => write!(f, "rolesets"),
+            Index::Counts => write!(f, "counts"),
+            Index::Positions => write!(f, "positions"),
+            Index::SkipList(role) => write!(f, "skip list for {role}"),
+            Index::Ancestors => write!(f, "ancestors"),
+        }
+    }
+}
+
+/// The result of fitting a [`TreeConfig`] within a memory budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetReport {
+    /// The indexes which fit.
+    pub config: TreeConfig,
+    /// The estimated memory used by [`BudgetReport::config`], in bytes.
+    pub bytes: usize,
+    /// The indexes which did not fit, and their estimated size in bytes.
+    pub skipped: Vec<(Index, usize)>,
+}
+
+impl Display for BudgetReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "indexes use ~{} KiB", self.bytes / 1024)?;
+        if self.skipped.is_empty() {
+            return write!(f, "; nothing skipped");
+        }
+        write!(f, "; skipped:")?;
+        for (index, bytes) in &self.skipped {
+            write!(f, "\n- {index} (~{} KiB)", bytes / 1024)?;
+        }
+        Ok(())
+    }
+}
+
+impl TreeConfig {
+    /// Returns the indexes in this config, from the most to the least useful.
+    ///
+    /// Rolesets come first, since most methods use them and they are nearly free; then skip
+    /// lists for roles in `profile`, from the most queried; then positions and ancestors; then
+    /// skip lists for roles which were never queried; and finally counts, the largest index.
+    #[must_use]
+    pub fn indexes(self, profile: &UsageProfile) -> Vec<Index> {
+        let (hot, cold): (Vec<_>, Vec<_>) = profile
+            .hottest(self.skip_lists)
+            .into_iter()
+            // `Role::Invalid` has no bit of its own, so it has no skip list
+            .filter(|role| !RoleSet::from(*role).is_empty())
+            .partition(|role| profile.count(*role) > 0);
+        let flag = |enabled: bool, index: Index| enabled.then_some(index);
+        flag(self.rolesets, Index::Rolesets)
+            .into_iter()
+            .chain(hot.into_iter().map(Index::SkipList))
+            .chain(flag(self.positions, Index::Positions))
+            .chain(flag(self.ancestors, Index::Ancestors))
+            .chain(cold.into_iter().map(Index::SkipList))
+            .chain(flag(self.counts, Index::Counts))
+            .collect()
+    }
+    /// Keep the most useful indexes in this config (see [`TreeConfig::indexes`]) whose estimated
+    /// size (see [`Index::bytes`]) fits within `budget` bytes on a tree of `nodes` nodes.
+    ///
+    /// An index which does not fit is skipped, but smaller, less useful indexes may still fit.
+    ///
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::{Index, TreeConfig, UsageProfile};
+    /// let mut profile = UsageProfile::default();
+    /// profile.record(Role::Heading);
+    /// let report = TreeConfig::ALL.within_budget(1_000_000, 20_000_000, &profile);
+    /// assert!(report.config.rolesets);
+    /// assert!(report.config.skip_lists.contains(Role::Heading.into()));
+    /// assert!(!report.config.counts);
+    /// assert!(report.skipped.iter().any(|(index, _)| *index == Index::Counts));
+    /// ```
+    #[must_use]
+    pub fn within_budget(
+        self,
+        nodes: usize,
+        budget: usize,
+        profile: &UsageProfile,
+    ) -> BudgetReport {
+        let mut report = BudgetReport {
+            config: self,
+            bytes: 0,
+            skipped: Vec::new(),
+        };
+        for index in self.indexes(profile) {
+            let bytes = index.bytes(nodes);
+            if report.bytes + bytes <= budget {
+                report.bytes += bytes;
+                continue;
+            }
+            report.skipped.push((index, bytes));
+            let config = &mut report.config;
+            match index {
+                Index::Rolesets => config.rolesets = false,
+                Index::Counts => config.counts = false,
+                Index::Positions => config.positions = false,
+                Index::SkipList(role) => config.skip_lists &= !RoleSet::from(role),
+                Index::Ancestors => config.ancestors = false,
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Index, UsageProfile};
+    use crate::{RoleSet, TreeConfig};
+    use atspi_common::Role;
+
+    #[test]
+    fn budget_prefers_hot_roles() {
+        let nodes = 1000;
+        let mut profile = UsageProfile::default();
+        profile.record(Role::Link);
+        profile.record(Role::Heading);
+        profile.record(Role::Heading);
+        let config = TreeConfig {
+            skip_lists: RoleSet::EMPTY | Role::Link | Role::Heading | Role::Table,
+            ..TreeConfig::NONE
+        };
+        assert_eq!(
+            config.indexes(&profile),
+            [Role::Heading, Role::Link, Role::Table].map(Index::SkipList)
+        );
+        // room for exactly two skip lists
+        let budget = 2 * Index::SkipList(Role::Heading).bytes(nodes);
+        let report = config.within_budget(nodes, budget, &profile);
+        assert_eq!(
+            report.config.skip_lists,
+            RoleSet::EMPTY | Role::Heading | Role::Link
+        );
+        assert_eq!(report.bytes, budget);
+        assert_eq!(
+            report.skipped,
+            vec![(Index::SkipList(Role::Table), budget / 2)]
+        );
+        // everything fits
+        let report = TreeConfig::ALL.within_budget(nodes, usize::MAX, &profile);
+        assert_eq!(report.config, TreeConfig::ALL);
+        assert!(report.skipped.is_empty());
+    }
+}
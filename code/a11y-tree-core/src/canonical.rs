@@ -0,0 +1,183 @@
+//! Order-invariant comparisons of snapshots.
+//!
+//! Some toolkits return children in a different order on each harvest, which makes diffs of
+//! otherwise identical snapshots noisy.
+//! Comparing with [`ChildOrder::Sorted`] sorts children into a canonical order first, so that
+//! only real changes show up.
+
+use crate::A11yNode;
+use std::cmp::Ordering;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Whether the order of children matters when comparing or hashing snapshots.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChildOrder {
+    /// Children are compared in the order they were harvested.
+    #[default]
+    AsIs,
+    /// Children are sorted by role, then name, then structure, before comparing.
+    Sorted,
+}
+
+impl A11yNode {
+    /// Stably sorts the children of every node by role, then name, then structure; afterwards,
+    /// two snapshots which differ only in the order of children are equal.
+    ///
+    /// ```
+    /// use a11y_tree_core::A11yNode;
+    /// let mut a: A11yNode = serde_json::from_str(r#"{"role": "List", "children": [
+    ///     {"role": "ListItem", "name": "B", "children": []},
+    ///     {"role": "ListItem", "name": "A", "children": []}
+    /// ]}"#).unwrap();
+    /// let mut b: A11yNode = serde_json::from_str(r#"{"role": "List", "children": [
+    ///     {"role": "ListItem", "name": "A", "children": []},
+    ///     {"role": "ListItem", "name": "B", "children": []}
+    /// ]}"#).unwrap();
+    /// assert_ne!(a, b);
+    /// a.canonicalize();
+    /// b.canonicalize();
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn canonicalize(&mut self) {
+        self.canonicalize_hashed();
+    }
+    /// Canonicalize, returning the structural hash of the canonical subtree.
+    fn canonicalize_hashed(&mut self) -> u64 {
+        let mut children = std::mem::take(&mut self.children)
+            .into_iter()
+            .map(|mut child| (child.canonicalize_hashed(), child))
+            .collect::<Vec<_>>();
+        children.sort_by(|(a_hash, a), (b_hash, b)| canonical_cmp(a, b).then(a_hash.cmp(b_hash)));
+        let hash = self.hash_with(children.iter().map(|(hash, _)| *hash));
+        self.children = children.into_iter().map(|(_, child)| child).collect();
+        hash
+    }
+    /// Returns a hash of the subtree's roles, names and shape.
+    ///
+    /// With [`ChildOrder::Sorted`], snapshots which differ only in the order of children have
+    /// the same hash.
+    /// Hashes are only comparable between runs of the same build.
+    #[must_use]
+    pub fn structural_hash(&self, order: ChildOrder) -> u64 {
+        match order {
+            ChildOrder::AsIs => {
+                self.hash_with(self.children.iter().map(|c| c.structural_hash(order)))
+            }
+            ChildOrder::Sorted => self.clone().canonicalize_hashed(),
+        }
+    }
+    fn hash_with(&self, children: impl Iterator<Item = u64>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.role as u32).hash(&mut hasher);
+        self.name.hash(&mut hasher);
+        for child in children {
+            child.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+    /// Returns the path (see [`crate::TreeTraversal::path`]) to the first node, in document
+    /// order, which differs between the two snapshots in role, name or number of children; or
+    /// `None` if they are equal.
+    ///
+    /// With [`ChildOrder::Sorted`], paths are into the canonicalized snapshots.
+    ///
+    /// ```
+    /// use a11y_tree_core::{canonical::ChildOrder, A11yNode};
+    /// let a: A11yNode = serde_json::from_str(r#"{"role": "List", "children": [
+    ///     {"role": "ListItem", "name": "B", "children": []},
+    ///     {"role": "ListItem", "name": "A", "children": []}
+    /// ]}"#).unwrap();
+    /// let b: A11yNode = serde_json::from_str(r#"{"role": "List", "children": [
+    ///     {"role": "ListItem", "name": "A", "children": []},
+    ///     {"role": "ListItem", "name": "B", "children": []}
+    /// ]}"#).unwrap();
+    /// assert_eq!(a.first_difference(&b, ChildOrder::AsIs), Some(vec![0]));
+    /// assert_eq!(a.first_difference(&b, ChildOrder::Sorted), None);
+    /// ```
+    #[must_use]
+    pub fn first_difference(&self, other: &A11yNode, order: ChildOrder) -> Option<Vec<usize>> {
+        match order {
+            ChildOrder::AsIs => first_difference(self, other),
+            ChildOrder::Sorted => {
+                let (mut a, mut b) = (self.clone(), other.clone());
+                a.canonicalize();
+                b.canonicalize();
+                first_difference(&a, &b)
+            }
+        }
+    }
+}
+
+/// The canonical order of two (canonicalized) siblings, ignoring their descendants.
+fn canonical_cmp(a: &A11yNode, b: &A11yNode) -> Ordering {
+    (a.role as u32)
+        .cmp(&(b.role as u32))
+        .then_with(|| a.name.cmp(&b.name))
+}
+
+fn first_difference(a: &A11yNode, b: &A11yNode) -> Option<Vec<usize>> {
+    if a.role != b.role || a.name != b.name || a.children.len() != b.children.len() {
+        return Some(Vec::new());
+    }
+    a.children
+        .iter()
+        .zip(&b.children)
+        .enumerate()
+        .find_map(|(i, (a, b))| {
+            let mut path = first_difference(a, b)?;
+            path.insert(0, i);
+            Some(path)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChildOrder;
+    use crate::A11yNode;
+
+    fn snapshot(json: &str) -> A11yNode {
+        serde_json::from_str(json).expect("Valid JSON")
+    }
+
+    #[test]
+    fn order_invariant() {
+        // the two "Section"s differ only in their children, so structure breaks the tie
+        let a = snapshot(
+            r#"{"role": "Frame", "children": [
+                {"role": "Section", "children": [{"role": "Link", "children": []}]},
+                {"role": "Section", "children": [{"role": "Heading", "children": []}]},
+                {"role": "Button", "name": "OK", "children": []}
+            ]}"#,
+        );
+        let b = snapshot(
+            r#"{"role": "Frame", "children": [
+                {"role": "Button", "name": "OK", "children": []},
+                {"role": "Section", "children": [{"role": "Heading", "children": []}]},
+                {"role": "Section", "children": [{"role": "Link", "children": []}]}
+            ]}"#,
+        );
+        assert_ne!(
+            a.structural_hash(ChildOrder::AsIs),
+            b.structural_hash(ChildOrder::AsIs)
+        );
+        assert_eq!(
+            a.structural_hash(ChildOrder::Sorted),
+            b.structural_hash(ChildOrder::Sorted)
+        );
+        assert_eq!(a.first_difference(&b, ChildOrder::Sorted), None);
+        // a real change is still found
+        let c = snapshot(
+            r#"{"role": "Frame", "children": [
+                {"role": "Button", "name": "Cancel", "children": []},
+                {"role": "Section", "children": [{"role": "Heading", "children": []}]},
+                {"role": "Section", "children": [{"role": "Link", "children": []}]}
+            ]}"#,
+        );
+        assert_ne!(
+            a.structural_hash(ChildOrder::Sorted),
+            c.structural_hash(ChildOrder::Sorted)
+        );
+        assert!(a.first_difference(&c, ChildOrder::Sorted).is_some());
+        assert_eq!(a.first_difference(&a, ChildOrder::AsIs), None);
+    }
+}
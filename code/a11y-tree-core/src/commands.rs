@@ -0,0 +1,301 @@
+//! The standard screen reader quick-navigation keys, and the query each one runs.
+//!
+//! Pressing a key moves to the next node with one of its roles; with Shift (i.e., the uppercase
+//! key), to the previous one.
+//! Keeping the table here means that every consumer agrees on what, e.g., "next form field"
+//! means.
+//!
+//! ```
+//! use a11y_tree_core::commands;
+//! use a11y_tree_core::{Tree, TreeConfig, TreeTraversal};
+//! # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+//! #     {"role": "Heading", "name": "Top", "children": []},
+//! #     {"role": "Link", "children": []},
+//! #     {"role": "Heading", "name": "Bottom", "children": []}
+//! # ]}"#).unwrap();
+//! let tree = Tree::from_root_node_with(root, TreeConfig::default());
+//! let next_heading = commands::query('h').unwrap();
+//! let top = next_heading.run(&tree, tree.root()).unwrap();
+//! let bottom = next_heading.run(&tree, top).unwrap();
+//! assert_eq!(tree.name(bottom), Some("Bottom"));
+//! assert_eq!(commands::query('H').unwrap().run(&tree, bottom), Some(top));
+//! ```
+
+use crate::policy::EmptinessPolicy;
+use crate::{HasRole, NodeIdExt, RoleSet, TreeTraversal};
+use atspi_common::Role;
+use indextree::{NodeEdge, NodeId};
+use itertools::Either;
+
+/// Which way a [`NavQuery`] moves through the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Towards the end of the document.
+    Next,
+    /// Towards the start of the document.
+    Previous,
+}
+
+/// A quick-navigation key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCommand {
+    /// The (lowercase) key.
+    pub key: char,
+    /// What the key moves to, for help text.
+    pub name: &'static str,
+    /// The roles the key moves to.
+    pub roles: RoleSet,
+    /// Whether to skip containers with nothing worth announcing (e.g., layout tables).
+    pub skip_empty: bool,
+}
+
+/// The quick-navigation keys, as used by most screen readers.
+pub const KEY_COMMANDS: &[KeyCommand] = &[
+    KeyCommand {
+        key: 'h',
+        name: "heading",
+        roles: RoleSet::from_roles(&[Role::Heading]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'k',
+        name: "link",
+        roles: RoleSet::from_roles(&[Role::Link]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'b',
+        name: "button",
+        roles: RoleSet::from_roles(&[Role::Button, Role::PushButtonMenu, Role::ToggleButton]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'f',
+        name: "form field",
+        roles: RoleSet::from_roles(&[
+            Role::CheckBox,
+            Role::ComboBox,
+            Role::Entry,
+            Role::ListBox,
+            Role::PasswordText,
+            Role::RadioButton,
+            Role::Slider,
+            Role::SpinButton,
+        ]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'e',
+        name: "edit field",
+        roles: RoleSet::from_roles(&[Role::Entry, Role::PasswordText]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'c',
+        name: "combo box",
+        roles: RoleSet::from_roles(&[Role::ComboBox]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'x',
+        name: "check box",
+        roles: RoleSet::from_roles(&[Role::CheckBox]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'r',
+        name: "radio button",
+        roles: RoleSet::from_roles(&[Role::RadioButton]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 't',
+        name: "table",
+        roles: RoleSet::from_roles(&[Role::Table, Role::TreeTable]),
+        skip_empty: true,
+    },
+    KeyCommand {
+        key: 'l',
+        name: "list",
+        roles: RoleSet::from_roles(&[Role::DescriptionList, Role::List]),
+        skip_empty: true,
+    },
+    KeyCommand {
+        key: 'i',
+        name: "list item",
+        roles: RoleSet::from_roles(&[Role::ListItem]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'd',
+        name: "landmark",
+        roles: RoleSet::from_roles(&[Role::Landmark]),
+        skip_empty: true,
+    },
+    KeyCommand {
+        key: 'q',
+        name: "block quote",
+        roles: RoleSet::from_roles(&[Role::BlockQuote]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'p',
+        name: "paragraph",
+        roles: RoleSet::from_roles(&[Role::Paragraph]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'g',
+        name: "graphic",
+        roles: RoleSet::from_roles(&[Role::Image]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 's',
+        name: "separator",
+        roles: RoleSet::from_roles(&[Role::Separator]),
+        skip_empty: false,
+    },
+    KeyCommand {
+        key: 'o',
+        name: "embedded object",
+        roles: RoleSet::from_roles(&[Role::Embedded]),
+        skip_empty: false,
+    },
+];
+
+/// What to look for when a quick-navigation key is pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NavQuery {
+    /// The roles to move to.
+    pub roles: RoleSet,
+    /// Which way to move.
+    pub direction: Direction,
+    /// If set, nodes whose subtree is effectively empty under this policy are skipped.
+    pub skip_empty: Option<EmptinessPolicy>,
+}
+
+/// The query for `key`: lowercase moves to the next match, uppercase to the previous one.
+#[must_use]
+pub fn query(key: char) -> Option<NavQuery> {
+    let direction = if key.is_uppercase() {
+        Direction::Previous
+    } else {
+        Direction::Next
+    };
+    let key = key.to_ascii_lowercase();
+    KEY_COMMANDS
+        .iter()
+        .find(|command| command.key == key)
+        .map(|command| NavQuery {
+            roles: command.roles,
+            direction,
+            skip_empty: command.skip_empty.then(EmptinessPolicy::default),
+        })
+}
+
+impl NavQuery {
+    /// Returns the nearest match strictly before or after `from` in document order.
+    ///
+    /// # Panics
+    ///
+    /// If `from` is not a valid ID in `tree`.
+    pub fn run<T: TreeTraversal>(&self, tree: &T, from: NodeId) -> Option<NodeId> {
+        let arena = tree.arena();
+        let matches = |id: &NodeId| {
+            self.roles.contains(arena[*id].get().role().into())
+                && self
+                    .skip_empty
+                    .is_none_or(|policy| !tree.is_effectively_empty(*id, policy))
+        };
+        // without rolesets, every subtree might contain a match
+        let worth_visiting = |id: NodeId| {
+            !tree.config().rolesets || arena[id].get().roleset().intersects(self.roles)
+        };
+        match self.direction {
+            Direction::Next => {
+                let subtree = |id: NodeId| {
+                    if tree.config().rolesets {
+                        Either::Left(NodeIdExt::descendants_any(id, arena, self.roles))
+                    } else {
+                        Either::Right(id.descendants(arena))
+                    }
+                };
+                let following = from
+                    .ancestors(arena)
+                    .flat_map(|anc| anc.following_siblings(arena).skip(1))
+                    .filter(|sib| worth_visiting(*sib))
+                    .flat_map(subtree);
+                subtree(from).skip(1).chain(following).find(matches)
+            }
+            Direction::Previous => {
+                // reverse document order: each preceding sibling's subtree (last node first),
+                // then the parent, for `from` and then each of its ancestors
+                let mut preceding = from.ancestors(arena).flat_map(|anc| {
+                    anc.preceding_siblings(arena)
+                        .skip(1)
+                        .filter(|sib| worth_visiting(*sib))
+                        .flat_map(|sib| {
+                            sib.reverse_traverse(arena).filter_map(|edge| match edge {
+                                NodeEdge::Start(id) => Some(id),
+                                NodeEdge::End(_) => None,
+                            })
+                        })
+                        .chain(arena[anc].parent())
+                });
+                preceding.find(matches)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{query, KEY_COMMANDS};
+    use crate::{A11yNode, Tree, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+
+    #[test]
+    fn key_commands() {
+        let node = |role, children| A11yNode {
+            role,
+            name: None,
+            toolkit: None,
+            children,
+        };
+        let root = node(
+            Role::Frame,
+            vec![
+                node(Role::List, vec![]),
+                node(Role::Heading, vec![]),
+                node(
+                    Role::Section,
+                    vec![node(
+                        Role::List,
+                        vec![node(Role::ListItem, vec![node(Role::Label, vec![])])],
+                    )],
+                ),
+                node(Role::Entry, vec![]),
+            ],
+        );
+        for config in [TreeConfig::NONE, TreeConfig::default()] {
+            let t = Tree::from_root_node_with(root.clone(), config);
+            let ids = t.root().descendants(&t.inner).collect::<Vec<_>>();
+            let run = |key, from| query(key).expect("Valid key").run(&t, from);
+            // the first list is empty, so it is skipped
+            assert_eq!(run('l', t.root()), Some(ids[4]));
+            assert_eq!(run('L', ids[7]), Some(ids[4]));
+            assert_eq!(run('L', ids[4]), None);
+            assert_eq!(run('i', t.root()), Some(ids[5]));
+            assert_eq!(run('h', ids[4]), None);
+            assert_eq!(run('H', ids[7]), Some(ids[2]));
+            assert_eq!(run('f', ids[2]), Some(ids[7]));
+            assert_eq!(run('F', ids[7]), None);
+        }
+        assert!(query('z').is_none());
+        // every key is bound once
+        for (i, command) in KEY_COMMANDS.iter().enumerate() {
+            assert!(KEY_COMMANDS[i + 1..].iter().all(|c| c.key != command.key));
+        }
+    }
+}
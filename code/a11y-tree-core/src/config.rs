@@ -0,0 +1,54 @@
+use crate::RoleSet;
+use serde::{Deserialize, Serialize};
+
+/// Which indexes a [`crate::Tree`] builds and keeps up to date.
+///
+/// Each index speeds up some queries, at the cost of memory and build time; pass this to
+/// [`crate::Tree::from_root_node_with`] to only pay for the indexes a consumer uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct TreeConfig {
+    /// A [`RoleSet`] of all descendants' roles on each node, and the first node with each role.
+    /// Used by all `*_roleset` and `*_cached` methods, and subtree queries.
+    pub rolesets: bool,
+    /// The number of descendants with each role on each node, as kept by
+    /// [`crate::TreeCount`].
+    /// Makes `how_many_roleset`, `how_many_in`, and `role_counts_in` lookups.
+    pub counts: bool,
+    /// The document order position of each node, making [`crate::Tree::precedes`] a lookup.
+    pub positions: bool,
+    /// Roles to keep "next node with this role" pointers for; see
+    /// [`crate::Tree::set_skip_lists`].
+    pub skip_lists: RoleSet,
+    /// The number of ancestors of each node, making [`crate::Tree::depth`] a lookup.
+    pub ancestors: bool,
+}
+
+impl TreeConfig {
+    /// No indexes; every query visits nodes.
+    pub const NONE: TreeConfig = TreeConfig {
+        rolesets: false,
+        counts: false,
+        positions: false,
+        skip_lists: RoleSet::EMPTY,
+        ancestors: false,
+    };
+    /// Every index, with skip lists for all roles.
+    pub const ALL: TreeConfig = TreeConfig {
+        rolesets: true,
+        counts: true,
+        positions: true,
+        skip_lists: RoleSet::ALL,
+        ancestors: true,
+    };
+}
+
+impl Default for TreeConfig {
+    /// Only rolesets; the same indexes as [`crate::TreeTraversal::build_rolesets`].
+    fn default() -> Self {
+        TreeConfig {
+            rolesets: true,
+            ..TreeConfig::NONE
+        }
+    }
+}
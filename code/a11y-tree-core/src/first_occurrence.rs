@@ -0,0 +1,45 @@
+use crate::role_set::ROLE_BITS;
+use atspi_common::Role;
+use indextree::NodeId;
+use serde::{Deserialize, Serialize};
+
+/// The first node, in document (pre-)order, with each role.
+///
+/// Built alongside the rolesets (see [`crate::TreeTraversal::build_rolesets`]), so that finding
+/// the first node with a role is a single lookup instead of a traversal.
+/// An empty table means the tree has not been indexed yet.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstOccurrence(Vec<Option<NodeId>>);
+
+impl FirstOccurrence {
+    /// Creates a table with no entries, marking the tree as indexed.
+    #[must_use]
+    pub fn indexed() -> Self {
+        FirstOccurrence(vec![None; ROLE_BITS])
+    }
+    /// Whether the table has been built; if not, every lookup returns `None`.
+    #[must_use]
+    pub fn is_indexed(&self) -> bool {
+        !self.0.is_empty()
+    }
+    /// Returns the first node with `role`, if any.
+    #[must_use]
+    pub fn get(&self, role: Role) -> Option<NodeId> {
+        self.0.get(role as usize).copied().flatten()
+    }
+    /// Sets the first node with `role`.
+    ///
+    /// Does nothing if the table has not been built.
+    pub fn set(&mut self, role: Role, node: Option<NodeId>) {
+        if let Some(slot) = self.0.get_mut(role as usize) {
+            *slot = node;
+        }
+    }
+    /// Records `node` as having `role`, if no earlier node with `role` has been seen.
+    /// Used when visiting nodes in document order.
+    pub fn record(&mut self, role: Role, node: NodeId) {
+        if self.get(role).is_none() {
+            self.set(role, Some(node));
+        }
+    }
+}
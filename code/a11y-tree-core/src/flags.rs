@@ -0,0 +1,103 @@
+//! Per-node flags, for keeping state on nodes (e.g., "already read") without a parallel data
+//! structure.
+
+use core::fmt;
+use core::ops::{BitAnd, BitOr, Not};
+use serde::{Deserialize, Serialize};
+
+/// A set of flags on a node; see [`crate::Tree::set_flags`].
+///
+/// What each flag means is up to the caller: the constants are the conventional uses, and
+/// [`Flags::custom`] gives the remaining bits.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// No flags.
+    pub const EMPTY: Flags = Flags(0);
+    /// The user has visited (e.g., read) the node.
+    pub const VISITED: Flags = Flags(1);
+    /// A policy has decided to skip the node.
+    pub const IGNORED: Flags = Flags(1 << 1);
+    /// The user has marked the node (e.g., as a bookmark).
+    pub const MARKED: Flags = Flags(1 << 2);
+    /// The node may be out of date with the application.
+    pub const STALE: Flags = Flags(1 << 3);
+    /// The number of flags available through [`Flags::custom`].
+    pub const CUSTOM: u8 = 4;
+
+    /// A flag with caller-defined meaning.
+    ///
+    /// ```
+    /// use a11y_tree_core::flags::Flags;
+    /// const SPOKEN: Flags = Flags::custom(0);
+    /// assert!(!SPOKEN.intersects(Flags::VISITED | Flags::IGNORED | Flags::MARKED | Flags::STALE));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not less than [`Flags::CUSTOM`].
+    #[must_use]
+    pub const fn custom(n: u8) -> Flags {
+        assert!(n < Flags::CUSTOM, "Custom flag out of range");
+        Flags(1 << (8 - Flags::CUSTOM + n))
+    }
+    /// Check if all flags in `other` are set.
+    #[must_use]
+    pub fn contains(self, other: Flags) -> bool {
+        self & other == other
+    }
+    /// Check if _any_ of the flags in `other` are set.
+    #[must_use]
+    pub fn intersects(self, other: Flags) -> bool {
+        !(self & other).is_empty()
+    }
+    /// Check if no flags are set.
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self == Flags::EMPTY
+    }
+}
+
+impl fmt::Debug for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Flags({:#010b})", self.0)
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Flags {
+    type Output = Flags;
+    fn bitand(self, rhs: Flags) -> Flags {
+        Flags(self.0 & rhs.0)
+    }
+}
+
+impl Not for Flags {
+    type Output = Flags;
+    fn not(self) -> Flags {
+        Flags(!self.0)
+    }
+}
+
+/// A node's own [`Flags`] (low byte), and those of any node in its subtree (high byte).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FlagWord(u16);
+
+impl FlagWord {
+    pub(crate) fn own(self) -> Flags {
+        Flags(self.0.to_le_bytes()[0])
+    }
+    pub(crate) fn subtree(self) -> Flags {
+        Flags(self.0.to_le_bytes()[1])
+    }
+    pub(crate) fn new(own: Flags, subtree: Flags) -> Self {
+        FlagWord(u16::from_le_bytes([own.0, subtree.0]))
+    }
+}
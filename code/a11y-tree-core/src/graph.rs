@@ -0,0 +1,356 @@
+//! Importing snapshots from a flat, graph-shaped format.
+//!
+//! Over AT-SPI, children are references to other objects, so a harvester naturally produces a
+//! list of nodes which refer to their children by index.
+//! Nothing stops a broken application from making a node its own descendant, or listing a child
+//! twice; building a tree from that would hang or duplicate subtrees, so [`FlatSnapshot::to_tree`]
+//! checks every edge as it goes.
+
+use crate::limits::{Exceeded, Guard, LimitError, Limits};
+use crate::A11yNode;
+use atspi_common::Role;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// A snapshot as a list of nodes which refer to their children by index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlatSnapshot {
+    /// The index of the root node.
+    pub root: usize,
+    /// Every node; those not reachable from the root are ignored.
+    pub nodes: Vec<FlatNode>,
+}
+
+/// A node in a [`FlatSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlatNode {
+    /// The role of the node.
+    pub role: Role,
+    /// The accessible name of the node, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The toolkit of an application's root node, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolkit: Option<String>,
+    /// The indexes of the node's children.
+    #[serde(default)]
+    pub children: Vec<usize>,
+}
+
+/// What to do with an edge which would not make a tree.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OnMalformed {
+    /// Stop at the first bad edge.
+    #[default]
+    Reject,
+    /// Drop every bad edge, keeping the first edge to each node in document order.
+    Repair,
+}
+
+/// Why an edge would not make a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformation {
+    /// The child is also an ancestor.
+    Cycle(usize),
+    /// The child was already reached through another edge.
+    DuplicateEdge(usize),
+    /// The child is not in the snapshot.
+    Dangling(usize),
+    /// The child would make the tree exceed the [`Limits`] given to [`FlatSnapshot::to_tree`];
+    /// this is never repaired.
+    Limit(Exceeded),
+}
+
+/// A bad edge in a [`FlatSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportError {
+    /// What is wrong with the edge.
+    pub malformation: Malformation,
+    /// The path (indexes into each node's `children`, from the root) of the edge.
+    pub path: Vec<usize>,
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.malformation {
+            Malformation::Cycle(child) => write!(f, "node {child} is its own ancestor")?,
+            Malformation::DuplicateEdge(child) => write!(f, "node {child} has more than one edge")?,
+            Malformation::Dangling(child) => write!(f, "node {child} does not exist")?,
+            Malformation::Limit(exceeded) => write!(f, "{exceeded}")?,
+        }
+        write!(f, " (at path {:?})", self.path)
+    }
+}
+
+impl Error for ImportError {}
+
+/// A tree imported from a [`FlatSnapshot`], and the edges which were dropped to make it one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The root of the tree.
+    pub root: A11yNode,
+    /// Every edge dropped by [`OnMalformed::Repair`], in document order.
+    pub repaired: Vec<ImportError>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Unvisited,
+    /// On the path from the root to the node being built.
+    Open,
+    Done,
+}
+
+/// A node whose children are being built.
+struct Frame {
+    index: usize,
+    /// The index (into the node's `children`) of the next child to build.
+    next: usize,
+    children: Vec<A11yNode>,
+}
+
+/// The state of a [`FlatSnapshot::to_tree`] in progress.
+struct Importer<'a> {
+    nodes: &'a [FlatNode],
+    visits: Vec<Visit>,
+    on_malformed: OnMalformed,
+    guard: Guard,
+    repaired: Vec<ImportError>,
+}
+
+impl Importer<'_> {
+    /// Start building the node at `index`, reached through `path`.
+    fn open(&mut self, index: usize, path: &[usize]) -> Result<Frame, ImportError> {
+        let node = &self.nodes[index];
+        let exceeded = |err: LimitError| ImportError {
+            malformation: Malformation::Limit(err.exceeded),
+            path: err.path,
+        };
+        self.guard.add_node(path).map_err(exceeded)?;
+        self.guard
+            .add_bytes(node.name.as_ref().map_or(0, String::len), path)
+            .map_err(exceeded)?;
+        self.visits[index] = Visit::Open;
+        Ok(Frame {
+            index,
+            next: 0,
+            children: Vec::with_capacity(node.children.len()),
+        })
+    }
+    /// Build the tree rooted at `root`, in document order.
+    ///
+    /// This keeps its own stack of the nodes being built rather than recursing, since a broken
+    /// application can make a chain of nodes far deeper than the thread's stack.
+    fn build(&mut self, root: usize) -> Result<A11yNode, ImportError> {
+        let nodes = self.nodes;
+        let mut path = Vec::new();
+        let mut stack = vec![self.open(root, &path)?];
+        loop {
+            let frame = stack
+                .last_mut()
+                .expect("The root is only popped at the end");
+            let node = &nodes[frame.index];
+            let Some(&child) = node.children.get(frame.next) else {
+                // every child is built, so the node is too
+                let frame = stack.pop().expect("Not empty");
+                self.visits[frame.index] = Visit::Done;
+                let built = A11yNode {
+                    role: node.role,
+                    name: node.name.clone(),
+                    toolkit: node.toolkit.clone(),
+                    children: frame.children,
+                };
+                let Some(parent) = stack.last_mut() else {
+                    return Ok(built);
+                };
+                parent.children.push(built);
+                path.pop();
+                continue;
+            };
+            path.push(frame.next);
+            frame.next += 1;
+            let malformation = match self.visits.get(child) {
+                None => Malformation::Dangling(child),
+                Some(Visit::Open) => Malformation::Cycle(child),
+                Some(Visit::Done) => Malformation::DuplicateEdge(child),
+                Some(Visit::Unvisited) => {
+                    // the child's path stays on `path` until it is built
+                    let frame = self.open(child, &path)?;
+                    stack.push(frame);
+                    continue;
+                }
+            };
+            let err = ImportError {
+                malformation,
+                path: path.clone(),
+            };
+            if self.on_malformed == OnMalformed::Reject {
+                return Err(err);
+            }
+            self.repaired.push(err);
+            path.pop();
+        }
+    }
+}
+
+impl FlatSnapshot {
+    /// Build a tree from the root, checking that every edge leads to a node not yet reached, and
+    /// that the tree stays within `limits`.
+    ///
+    /// ```
+    /// use a11y_tree_core::graph::{FlatSnapshot, Malformation, OnMalformed};
+    /// use a11y_tree_core::limits::Limits;
+    /// let flat: FlatSnapshot = serde_json::from_str(r#"{"root": 0, "nodes": [
+    ///     {"role": "List", "children": [1, 2]},
+    ///     {"role": "ListItem"},
+    ///     {"role": "ListItem", "children": [0]}
+    /// ]}"#).unwrap();
+    /// let err = flat.to_tree(OnMalformed::Reject, Limits::NONE).unwrap_err();
+    /// assert_eq!(err.malformation, Malformation::Cycle(0));
+    /// assert_eq!(err.path, vec![1, 0]);
+    /// let import = flat.to_tree(OnMalformed::Repair, Limits::NONE).unwrap();
+    /// assert_eq!(import.root.children().len(), 2);
+    /// assert_eq!(import.repaired, vec![err]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// With [`OnMalformed::Reject`], the first bad edge in document order; or, even when
+    /// repairing, a dangling root (with an empty path) or the first node which exceeds `limits`.
+    pub fn to_tree(
+        &self,
+        on_malformed: OnMalformed,
+        limits: Limits,
+    ) -> Result<Import, ImportError> {
+        if self.root >= self.nodes.len() {
+            return Err(ImportError {
+                malformation: Malformation::Dangling(self.root),
+                path: Vec::new(),
+            });
+        }
+        let mut importer = Importer {
+            nodes: &self.nodes,
+            visits: vec![Visit::Unvisited; self.nodes.len()],
+            on_malformed,
+            guard: Guard::new(limits),
+            repaired: Vec::new(),
+        };
+        let root = importer.build(self.root)?;
+        Ok(Import {
+            root,
+            repaired: importer.repaired,
+        })
+    }
+    /// Flatten a tree, numbering nodes in document order.
+    #[must_use]
+    pub fn from_tree(root: &A11yNode) -> Self {
+        let mut nodes = Vec::new();
+        // each node with the index of its parent; an explicit stack, as in `Importer::build`
+        let mut stack = vec![(root, None::<usize>)];
+        while let Some((node, parent)) = stack.pop() {
+            let index = nodes.len();
+            nodes.push(FlatNode {
+                role: node.role,
+                name: node.name.clone(),
+                toolkit: node.toolkit.clone(),
+                children: Vec::with_capacity(node.children.len()),
+            });
+            if let Some(parent) = parent {
+                nodes[parent].children.push(index);
+            }
+            // reversed, so the first child is numbered next
+            stack.extend(node.children.iter().rev().map(|child| (child, Some(index))));
+        }
+        FlatSnapshot { root: 0, nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlatNode, FlatSnapshot, ImportError, Malformation, OnMalformed};
+    use crate::limits::{self, Exceeded, Limits};
+    use crate::{Tree, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+
+    #[test]
+    fn import() {
+        let flat: FlatSnapshot = serde_json::from_str(
+            r#"{"root": 0, "nodes": [
+                {"role": "Frame", "children": [1, 3]},
+                {"role": "Panel", "children": [2, 2, 9]},
+                {"role": "Label", "name": "Hi", "children": [1]},
+                {"role": "Button", "children": [2]}
+            ]}"#,
+        )
+        .expect("Valid JSON");
+        let err = |malformation, path| ImportError { malformation, path };
+        assert_eq!(
+            flat.to_tree(OnMalformed::Reject, Limits::NONE),
+            Err(err(Malformation::Cycle(1), vec![0, 0, 0]))
+        );
+        let import = flat
+            .to_tree(OnMalformed::Repair, Limits::NONE)
+            .expect("Valid repair");
+        assert_eq!(
+            import.repaired,
+            vec![
+                err(Malformation::Cycle(1), vec![0, 0, 0]),
+                err(Malformation::DuplicateEdge(2), vec![0, 1]),
+                err(Malformation::Dangling(9), vec![0, 2]),
+                err(Malformation::DuplicateEdge(2), vec![1, 0]),
+            ]
+        );
+        // a repaired tree round-trips without further repairs
+        let again = FlatSnapshot::from_tree(&import.root)
+            .to_tree(OnMalformed::Reject, Limits::NONE)
+            .expect("Valid tree");
+        assert_eq!(again.root, import.root);
+        assert!(again.repaired.is_empty());
+        assert_eq!(again.root.children()[0].children()[0].name(), Some("Hi"));
+    }
+
+    #[test]
+    fn import_long_chain() {
+        // each node's only child is the next one
+        let chain = |len: usize| FlatSnapshot {
+            root: 0,
+            nodes: (0..len)
+                .map(|i| FlatNode {
+                    role: Role::Panel,
+                    name: None,
+                    toolkit: None,
+                    children: if i + 1 < len { vec![i + 1] } else { vec![] },
+                })
+                .collect(),
+        };
+        let limits = Limits {
+            max_depth: 1000,
+            ..Limits::NONE
+        };
+        let import = chain(1000)
+            .to_tree(OnMalformed::Reject, limits)
+            .expect("Valid chain");
+        assert_eq!(import.root.children().len(), 1);
+        // stopped at the first node too deep, without building the rest of the chain
+        let err = chain(1_000_000)
+            .to_tree(OnMalformed::Repair, limits)
+            .expect_err("Chain too deep");
+        assert_eq!(err.malformation, Malformation::Limit(Exceeded::Depth(1000)));
+        assert_eq!(err.path, vec![0; 1000]);
+        // far deeper than the thread's stack, so flattening, checking, building and dropping
+        // must not recurse either
+        let long = chain(1_000_000);
+        let import = long
+            .to_tree(OnMalformed::Reject, Limits::NONE)
+            .expect("Valid chain");
+        assert_eq!(FlatSnapshot::from_tree(&import.root), long);
+        assert_eq!(limits::check(&import.root, Limits::NONE), Ok(()));
+        drop(import);
+        let import = long
+            .to_tree(OnMalformed::Reject, Limits::NONE)
+            .expect("Valid chain");
+        let tree = Tree::from_root_node_with(import.root, TreeConfig::NONE);
+        assert_eq!(tree.nodes(), 1_000_000);
+    }
+}
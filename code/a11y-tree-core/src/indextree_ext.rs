@@ -0,0 +1,202 @@
+use crate::{flags::Flags, RoleSet};
+use atspi_common::Role;
+use indextree::{Arena, NodeEdge, NodeId};
+
+/// Take a [`NodeId`] and traverse it using a custom iterator.
+/// Only needed sequentially, since `rayon` provides [`rayon::iter::walk_tree`] which gives similar
+/// functionality in parallel.
+pub trait NodeIdExt {
+    /// Traverse descendants, ignoring subtrees whose roleset does not contain the given roleset.
+    fn descendants_role<T>(self, arena: &Arena<T>, role: RoleSet) -> DescendantsRole<'_, T>;
+    /// Traverse all nodes descendants first, then next siblings, then parent's next siblings, etc.
+    /// Ignoring all subtrees whose roleset does not contain the given roleset.
+    fn traverse_role<T>(self, arena: &Arena<T>, role: RoleSet) -> TraverseRole<'_, T>;
+    /// Traverse descendants, ignoring subtrees whose roleset is entirely covered by the given
+    /// roleset (i.e., every node in the subtree has one of the given roles).
+    fn descendants_without<T>(self, arena: &Arena<T>, roles: RoleSet) -> DescendantsRole<'_, T>;
+    /// Traverse descendants, ignoring subtrees in which no node has all of the given flags.
+    fn descendants_flagged<T>(self, arena: &Arena<T>, flags: Flags) -> DescendantsRole<'_, T>;
+    /// Traverse descendants, ignoring subtrees whose roleset contains none of the given roles.
+    fn descendants_any<T>(self, arena: &Arena<T>, roles: RoleSet) -> DescendantsRole<'_, T>;
+}
+
+impl NodeIdExt for NodeId {
+    fn descendants_role<T>(self, arena: &Arena<T>, role: RoleSet) -> DescendantsRole<'_, T> {
+        DescendantsRole::new(arena, self, role)
+    }
+    fn traverse_role<T>(self, arena: &Arena<T>, role: RoleSet) -> TraverseRole<'_, T> {
+        TraverseRole::new(arena, self, role)
+    }
+    fn descendants_without<T>(self, arena: &Arena<T>, roles: RoleSet) -> DescendantsRole<'_, T> {
+        DescendantsRole(TraverseRole::with_filter(
+            arena,
+            self,
+            RoleFilter::NotCoveredBy(roles),
+        ))
+    }
+    fn descendants_flagged<T>(self, arena: &Arena<T>, flags: Flags) -> DescendantsRole<'_, T> {
+        DescendantsRole(TraverseRole::with_filter(
+            arena,
+            self,
+            RoleFilter::Flagged(flags),
+        ))
+    }
+    fn descendants_any<T>(self, arena: &Arena<T>, roles: RoleSet) -> DescendantsRole<'_, T> {
+        DescendantsRole(TraverseRole::with_filter(
+            arena,
+            self,
+            RoleFilter::Intersecting(roles),
+        ))
+    }
+}
+
+/// Decides which subtrees a [`TraverseRole`] descends into, based on the subtree's roleset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RoleFilter {
+    /// Visit subtrees which contain all of the given roles.
+    Containing(RoleSet),
+    /// Visit subtrees which contain at least one role _not_ in the given roles.
+    NotCoveredBy(RoleSet),
+    /// Visit subtrees which contain a node with all of the given flags.
+    Flagged(Flags),
+    /// Visit subtrees which contain at least one of the given roles.
+    Intersecting(RoleSet),
+}
+impl RoleFilter {
+    fn keeps<T: HasRole>(self, node: &T) -> bool {
+        match self {
+            RoleFilter::Containing(roles) => node.roleset().contains(roles),
+            RoleFilter::NotCoveredBy(roles) => !roles.contains(node.roleset()),
+            RoleFilter::Flagged(flags) => node.subtree_flags().contains(flags),
+            RoleFilter::Intersecting(roles) => node.roleset().intersects(roles),
+        }
+    }
+}
+pub struct DescendantsRole<'a, T>(TraverseRole<'a, T>);
+
+impl<'a, T> DescendantsRole<'a, T> {
+    pub(crate) fn new(arena: &'a Arena<T>, current: NodeId, role: RoleSet) -> Self {
+        Self(TraverseRole::new(arena, current, role))
+    }
+}
+
+impl<T> Iterator for DescendantsRole<'_, T>
+where
+    T: HasRole,
+{
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        self.0.find_map(|edge| match edge {
+            NodeEdge::Start(node) => Some(node),
+            NodeEdge::End(_) => None,
+        })
+    }
+}
+
+impl<T> core::iter::FusedIterator for DescendantsRole<'_, T> where T: HasRole {}
+
+trait NodeEdgeExt {
+    fn next_traverse_role<T>(self, arena: &Arena<T>, filter: RoleFilter) -> Option<Self>
+    where
+        Self: Sized,
+        T: HasRole;
+}
+/// Indication that a type contains a [`RoleSet`].
+/// All inner [`crate::TreeTraversal::Node`] types must implement this so that the `RoleSet` can be
+/// accessed generically.
+pub trait HasRole {
+    /// Get the inner [`RoleSet`].
+    fn roleset(&self) -> RoleSet;
+    /// Get the node's own [`Role`].
+    fn role(&self) -> Role;
+    /// Get the node's own [`Flags`]; node types without flags have none.
+    fn flags(&self) -> Flags {
+        Flags::EMPTY
+    }
+    /// Get the [`Flags`] set on any node in this node's subtree (including itself).
+    fn subtree_flags(&self) -> Flags {
+        Flags::EMPTY
+    }
+}
+impl NodeEdgeExt for NodeEdge {
+    fn next_traverse_role<T>(self, arena: &Arena<T>, filter: RoleFilter) -> Option<Self>
+    where
+        Self: Sized,
+        T: HasRole,
+    {
+        match self {
+            NodeEdge::Start(node) => match arena[node].first_child() {
+                Some(first_child) => {
+                    if filter.keeps(arena[first_child].get()) {
+                        Some(NodeEdge::Start(first_child))
+                    } else {
+                        Some(NodeEdge::End(first_child))
+                    }
+                }
+                None => Some(NodeEdge::End(node)),
+            },
+            NodeEdge::End(mut node_id) => loop {
+                let node = &arena[node_id];
+                match node.next_sibling() {
+                    Some(next_sibling) => {
+                        if filter.keeps(arena[next_sibling].get()) {
+                            return Some(NodeEdge::Start(next_sibling));
+                        }
+                        // skip the sibling's subtree without recursing; wide nodes can have
+                        // thousands of children
+                        node_id = next_sibling;
+                    }
+                    // `node.parent()` here can only be `None` if the tree has
+                    // been modified during iteration, but silently stoping
+                    // iteration seems a more sensible behavior than panicking.
+                    None => return node.parent().map(NodeEdge::End),
+                }
+            },
+        }
+    }
+}
+pub struct TraverseRole<'a, T> {
+    arena: &'a Arena<T>,
+    root: NodeId,
+    next: Option<NodeEdge>,
+    filter: RoleFilter,
+}
+impl<'a, T> TraverseRole<'a, T> {
+    pub(crate) fn new(arena: &'a Arena<T>, current: NodeId, role: RoleSet) -> Self {
+        Self::with_filter(arena, current, RoleFilter::Containing(role))
+    }
+    fn with_filter(arena: &'a Arena<T>, current: NodeId, filter: RoleFilter) -> Self {
+        Self {
+            arena,
+            root: current,
+            next: Some(NodeEdge::Start(current)),
+            filter,
+        }
+    }
+
+    /// Calculates the next node.
+    fn next_of_next(&self, next: NodeEdge) -> Option<NodeEdge>
+    where
+        T: HasRole,
+    {
+        if next == NodeEdge::End(self.root) {
+            return None;
+        }
+        next.next_traverse_role(self.arena, self.filter)
+    }
+}
+
+impl<T> Iterator for TraverseRole<'_, T>
+where
+    T: HasRole,
+{
+    type Item = NodeEdge;
+
+    fn next(&mut self) -> Option<NodeEdge> {
+        let next = self.next.take()?;
+        self.next = self.next_of_next(next);
+        Some(next)
+    }
+}
+impl<T> core::iter::FusedIterator for TraverseRole<'_, T> where T: HasRole {}
@@ -0,0 +1,392 @@
+//! A write-optimized backend, whose rolesets are refreshed in batches.
+//!
+//! Keeping rolesets exact on every write costs a walk to the root per write, which adds up
+//! during write-heavy periods like page loads.
+//! [`LazyTree`] instead marks the ancestors of a change as stale (stopping at the first one
+//! which already is), and recomputes stale rolesets later in one batch.
+
+use crate::{A11yNode, HasRole, RoleSet, TreeConfig, TreeTraversal};
+use atspi_common::Role;
+use indextree::{Arena, NodeId};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// A node containing a role, a roleset for all descendants, and whether that roleset may be out
+/// of date.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LazyNode {
+    /// The node's role.
+    role: Role,
+    /// Roleset of all descendants, as of the last refresh.
+    roleset: RoleSet,
+    /// Whether a descendant has changed since the last refresh.
+    stale: bool,
+}
+
+impl HasRole for LazyNode {
+    /// A stale node's roleset is [`RoleSet::ALL`], so that traversals never skip it.
+    fn roleset(&self) -> RoleSet {
+        if self.stale {
+            RoleSet::ALL
+        } else {
+            self.roleset
+        }
+    }
+    fn role(&self) -> Role {
+        self.role
+    }
+}
+
+impl LazyNode {
+    /// Adds the created [`LazyNode`] (and its descendants) to a given arena, with their rolesets
+    /// computed; returns its new [`NodeId`].
+    fn from_a11y_node(node: A11yNode, tree: &mut Arena<LazyNode>) -> NodeId {
+        let id = crate::add_a11y_nodes(
+            node,
+            tree,
+            |node| LazyNode {
+                role: node.role,
+                roleset: node.role.into(),
+                stale: false,
+            },
+            &mut |_, _, _| {},
+        );
+        // children before parents, so each child's roleset is complete when it is added
+        for child_id in id
+            .descendants(tree)
+            .skip(1)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            let parent_id = tree[child_id].parent().expect("Valid parent node");
+            let child_roleset = tree[child_id].get().roleset;
+            tree[parent_id].get_mut().roleset |= child_roleset;
+        }
+        id
+    }
+}
+
+/// Whether a subtree contains a role, according to a possibly stale roleset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    /// The subtree contains the role.
+    Present,
+    /// The subtree does not contain the role.
+    Absent,
+    /// The subtree has changed since its roleset was last refreshed.
+    Unknown,
+}
+
+/// New rolesets for stale nodes, computed by [`LazyTree::compute_refresh`].
+#[derive(Debug)]
+pub struct Refresh {
+    /// The number of writes to the tree when the refresh was computed.
+    writes: u64,
+    rolesets: HashMap<NodeId, RoleSet>,
+}
+
+/// A tree whose rolesets are refreshed in batches, instead of on every write.
+///
+/// Queries never give wrong results: stale nodes are never pruned (see [`LazyNode`]'s
+/// [`HasRole::roleset`]), so queries only get slower until the next refresh.
+#[derive(Debug)]
+pub struct LazyTree {
+    inner: Arena<LazyNode>,
+    root: NodeId,
+    /// Whether [`TreeTraversal::build_rolesets`] has been called.
+    indexed: bool,
+    /// Nodes marked stale since the last refresh.
+    dirty: Vec<NodeId>,
+    /// The number of writes so far; used to discard refreshes computed before a write.
+    writes: u64,
+    /// Whether arena order is document order; unset once a subtree is appended.
+    arena_ordered: bool,
+}
+
+impl TreeTraversal for LazyTree {
+    type Node = LazyNode;
+    /// Rolesets are computed when nodes are created; this only refreshes stale ones.
+    fn build_rolesets(&mut self) {
+        self.indexed = true;
+        self.refresh();
+    }
+    fn from_root_node(root_node: A11yNode) -> Self {
+        let mut tree: Arena<LazyNode> = Arena::new();
+        let root_id = LazyNode::from_a11y_node(root_node, &mut tree);
+        LazyTree {
+            inner: tree,
+            root: root_id,
+            indexed: false,
+            dirty: Vec::new(),
+            writes: 0,
+            arena_ordered: true,
+        }
+    }
+    fn root(&self) -> NodeId {
+        self.root
+    }
+    fn arena(&self) -> &Arena<Self::Node> {
+        &self.inner
+    }
+    fn config(&self) -> TreeConfig {
+        TreeConfig {
+            rolesets: self.indexed,
+            ..TreeConfig::NONE
+        }
+    }
+    fn arena_in_document_order(&self) -> bool {
+        self.arena_ordered
+    }
+    fn unique_roles_in(&self, node: NodeId) -> RoleSet {
+        let this = self.inner[node].get();
+        if self.indexed && !this.stale {
+            return this.roleset;
+        }
+        // a stale subtree may contain fresh subtrees, whose rolesets can be used as-is
+        let mut roles = RoleSet::EMPTY;
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            let node = self.inner[id].get();
+            if node.stale || !self.indexed {
+                roles |= node.role;
+                stack.extend(id.children(&self.inner));
+            } else {
+                roles |= node.roleset;
+            }
+        }
+        roles
+    }
+    fn iter_lacking(
+        &self,
+        roles: RoleSet,
+    ) -> impl Iterator<Item = &indextree::Node<Self::Node>> + use<'_> {
+        self.root
+            .descendants(&self.inner)
+            .filter(move |node_id| !self.unique_roles_in(*node_id).intersects(roles))
+            .map(move |node_id| &self.inner[node_id])
+    }
+}
+
+impl LazyTree {
+    /// Returns whether the subtree rooted at `node` contains `role`, without visiting
+    /// descendants.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn presence(&self, node: NodeId, role: Role) -> Presence {
+        let node = self.inner[node].get();
+        if node.stale || !self.indexed {
+            Presence::Unknown
+        } else if node.roleset.contains(role.into()) {
+            Presence::Present
+        } else {
+            Presence::Absent
+        }
+    }
+    /// Returns the number of nodes which are stale until the next refresh.
+    #[must_use]
+    pub fn stale_nodes(&self) -> usize {
+        self.dirty.len()
+    }
+    /// Mark `node` and its ancestors stale, stopping at the first one which already is.
+    fn mark_stale(&mut self, node: NodeId) {
+        self.writes += 1;
+        for id in node.ancestors(&self.inner).collect::<Vec<_>>() {
+            let node = self.inner[id].get_mut();
+            if node.stale {
+                break;
+            }
+            node.stale = true;
+            self.dirty.push(id);
+        }
+    }
+    /// Appends `node` (and its children) as the last child of `parent`; returns the new node's
+    /// [`NodeId`].
+    ///
+    /// The new nodes' rolesets are exact; their ancestors are marked stale.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not a valid ID in this tree.
+    pub fn append_subtree(&mut self, parent: NodeId, node: A11yNode) -> NodeId {
+        let id = LazyNode::from_a11y_node(node, &mut self.inner);
+        parent.append(id, &mut self.inner);
+        self.arena_ordered = false;
+        self.mark_stale(parent);
+        id
+    }
+    /// Removes `node` and all its descendants; their ancestors are marked stale.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree, or is the root.
+    pub fn remove_subtree(&mut self, node: NodeId) {
+        assert_ne!(node, self.root, "Cannot remove the root node");
+        let parent = self.inner[node]
+            .parent()
+            .expect("Non-root node has a parent");
+        node.remove_subtree(&mut self.inner);
+        self.mark_stale(parent);
+    }
+    /// Compute new rolesets for all stale nodes, without changing the tree.
+    ///
+    /// Stale nodes are refreshed from the deepest up, so that each node's children are fresh;
+    /// nodes at the same depth are refreshed in parallel.
+    #[must_use]
+    pub fn compute_refresh(&self) -> Refresh {
+        let mut levels: Vec<Vec<NodeId>> = Vec::new();
+        for id in &self.dirty {
+            if id.is_removed(&self.inner) {
+                continue;
+            }
+            let depth = id.ancestors(&self.inner).count();
+            if levels.len() < depth {
+                levels.resize(depth, Vec::new());
+            }
+            levels[depth - 1].push(*id);
+        }
+        let mut rolesets = HashMap::with_capacity(self.dirty.len());
+        for level in levels.iter().rev() {
+            let computed = level
+                .par_iter()
+                .map(|id| {
+                    let roleset = id.children(&self.inner).fold(
+                        RoleSet::from(self.inner[*id].get().role),
+                        |roles, child| {
+                            roles
+                                | rolesets
+                                    .get(&child)
+                                    .copied()
+                                    .unwrap_or(self.inner[child].get().roleset)
+                        },
+                    );
+                    (*id, roleset)
+                })
+                .collect::<Vec<_>>();
+            rolesets.extend(computed);
+        }
+        Refresh {
+            writes: self.writes,
+            rolesets,
+        }
+    }
+    /// Apply a refresh computed by [`LazyTree::compute_refresh`]; returns whether it was applied.
+    ///
+    /// A refresh computed before the latest write is discarded, since it may be missing that
+    /// write.
+    pub fn apply_refresh(&mut self, refresh: Refresh) -> bool {
+        if refresh.writes != self.writes {
+            return false;
+        }
+        for (id, roleset) in refresh.rolesets {
+            let node = self.inner[id].get_mut();
+            node.roleset = roleset;
+            node.stale = false;
+        }
+        self.dirty.clear();
+        true
+    }
+    /// Refresh all stale rolesets.
+    pub fn refresh(&mut self) {
+        let refresh = self.compute_refresh();
+        self.apply_refresh(refresh);
+    }
+    /// Refresh all stale rolesets on the [`rayon`] thread pool.
+    ///
+    /// The new rolesets are computed while only holding a read lock, so queries can continue in
+    /// the meantime; the write lock is only held to apply them.
+    /// If the tree is written to in the meantime, the refresh is discarded.
+    ///
+    /// # Panics
+    ///
+    /// The spawned task panics if the lock is poisoned.
+    pub fn refresh_in_background(tree: &Arc<RwLock<LazyTree>>) {
+        let tree = Arc::clone(tree);
+        rayon::spawn(move || {
+            let refresh = tree.read().expect("Lock is not poisoned").compute_refresh();
+            tree.write()
+                .expect("Lock is not poisoned")
+                .apply_refresh(refresh);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LazyTree, Presence};
+    use crate::{A11yNode, TreeTraversal};
+    use atspi_common::Role;
+    use std::sync::{Arc, RwLock};
+
+    fn tree() -> LazyTree {
+        let root = serde_json::from_str(
+            r#"{"role": "Frame", "children": [
+                {"role": "List", "children": [{"role": "ListItem", "children": []}]},
+                {"role": "Panel", "children": []}
+            ]}"#,
+        )
+        .expect("Valid JSON");
+        let mut tree = LazyTree::from_root_node(root);
+        tree.build_rolesets();
+        tree
+    }
+
+    fn heading() -> A11yNode {
+        serde_json::from_str(r#"{"role": "Heading", "children": []}"#).expect("Valid JSON")
+    }
+
+    #[test]
+    fn stale_until_refresh() {
+        let mut tree = tree();
+        let root = tree.root();
+        let list = tree.arena()[root].first_child().expect("Has children");
+        let panel = tree.arena()[root].last_child().expect("Has children");
+        assert_eq!(tree.presence(root, Role::Heading), Presence::Absent);
+        tree.append_subtree(panel, heading());
+        assert_eq!(tree.presence(root, Role::Heading), Presence::Unknown);
+        assert_eq!(tree.presence(list, Role::Heading), Presence::Absent);
+        // queries are correct while stale
+        assert!(tree.find_first_roleset(Role::Heading).is_some());
+        assert_eq!(tree.how_many_roleset(Role::Heading), 1);
+        assert!(tree.subtree_contains(root, Role::Heading.into()));
+        assert_eq!(tree.stale_nodes(), 2);
+        // marking stops at the first stale ancestor
+        tree.append_subtree(panel, heading());
+        assert_eq!(tree.stale_nodes(), 2);
+        tree.remove_subtree(list);
+        assert_eq!(tree.stale_nodes(), 2);
+        tree.refresh();
+        assert_eq!(tree.stale_nodes(), 0);
+        assert_eq!(tree.presence(root, Role::Heading), Presence::Present);
+        assert_eq!(tree.presence(root, Role::ListItem), Presence::Absent);
+        assert_eq!(tree.unique_roles_roleset(), tree.unique_roles());
+    }
+
+    #[test]
+    fn background_refresh() {
+        let mut tree = tree();
+        let root = tree.root();
+        tree.append_subtree(root, heading());
+        // a refresh computed before a write is discarded
+        let refresh = tree.compute_refresh();
+        tree.append_subtree(root, heading());
+        assert!(!tree.apply_refresh(refresh));
+        assert_eq!(tree.presence(root, Role::Heading), Presence::Unknown);
+
+        let tree = Arc::new(RwLock::new(tree));
+        let (tx, rx) = std::sync::mpsc::channel();
+        LazyTree::refresh_in_background(&tree);
+        rayon::spawn(move || tx.send(()).expect("Receiver is alive"));
+        rx.recv().expect("Sender is alive");
+        // the pool may still be applying the refresh; wait for the write lock
+        while tree.read().expect("Lock is not poisoned").stale_nodes() > 0 {
+            std::thread::yield_now();
+        }
+        let tree = tree.read().expect("Lock is not poisoned");
+        assert_eq!(tree.presence(root, Role::Heading), Presence::Present);
+    }
+}
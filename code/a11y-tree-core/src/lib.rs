@@ -0,0 +1,1853 @@
+//! # `a11y_tree_core`
+//!
+//! This create is an experiment on how to increase the speed of [screen
+//! reader](https://en.wikipedia.org/wiki/Screen_reader) structural navigation commands useing
+//! role-based bitset propogation.
+//!
+//! Internally, this uses an arena-allocated tree and various methods of access to find the ideal
+//! method for use in the [Odilia screen reader project](https://odilia.app/).
+//!
+//! Most functions will have two variants:
+//!
+//! - `method_name`: sequential arena-allocated tree accessor
+//! - `par_method_name`: parallel arena-allocated tree accessor
+//!
+//! Some have an additional two:
+//!
+//! - `method_name_roleset`: sequential arena-allocated tree accessor, that uses the bitset
+//!   propogation fields to ignore un-needed subtrees.
+//! - `par_method_name_roleset`: parallel arena-allocated tree accessor, that uses the bitset
+//!   propogation fields to ignore un-needed subtrees.
+//!
+//! At this time, we only benchmark _accessors_ and not _modifications_.
+//! This is planned for the future, as accessibility trees are written _much_ more often than they
+//! are read.
+//!
+//! Check the benchmarks for results.
+//!
+#![deny(clippy::all, clippy::pedantic, unsafe_code, missing_docs, rustdoc::all)]
+
+pub mod budget;
+pub mod canonical;
+pub mod commands;
+mod config;
+mod first_occurrence;
+pub mod flags;
+pub mod graph;
+mod indextree_ext;
+pub mod lazy;
+pub mod limits;
+pub mod memory;
+pub mod order;
+#[cfg(test)]
+mod validity;
+pub use budget::{BudgetReport, Index, UsageProfile};
+pub use config::TreeConfig;
+pub use first_occurrence::FirstOccurrence;
+use flags::{FlagWord, Flags};
+pub use indextree_ext::{HasRole, NodeIdExt};
+use limits::{LimitError, Limits};
+use order::RoleOrder;
+mod plan;
+pub mod policy;
+pub mod prelude;
+pub mod query_trace;
+mod role_set;
+mod scenario;
+pub mod search;
+pub mod shard;
+mod skip_list;
+pub mod split;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod trace;
+use atspi_common::Role;
+pub use plan::QueryPlan;
+pub use policy::EmptinessPolicy;
+use policy::{Break, TextPolicy};
+use query_trace::QueryTrace;
+use rayon::iter::walk_tree_prefix;
+use rayon::prelude::*;
+use role_set::ROLE_BITS;
+pub use role_set::{RoleSet, RoleSetVecCount};
+pub use scenario::{parse_role, Scenario, Step, StepOutput};
+use serde::{Deserialize, Serialize};
+use skip_list::SkipLists;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use indextree::{Arena, NodeEdge, NodeId};
+use itertools::Either;
+
+/// A node containing a role, a roleset for all descendants, and a count of how many of each role
+/// in all descendants.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct NodeCount {
+    /// The node's role.
+    role: Role,
+    /// All descendants' roles and how many.
+    roleset: RoleSetVecCount,
+}
+impl HasRole for NodeCount {
+    fn roleset(&self) -> RoleSet {
+        self.roleset.1
+    }
+    fn role(&self) -> Role {
+        self.role
+    }
+}
+
+impl NodeCount {
+    /// Adds the created [`NodeCount`] to a given arena; returns its new [`NodeId`].
+    fn from_a11y_node(node: A11yNode, tree: &mut Arena<NodeCount>) -> NodeId {
+        add_a11y_nodes(
+            node,
+            tree,
+            |node| NodeCount {
+                role: node.role,
+                roleset: RoleSetVecCount::default(),
+            },
+            &mut |_, _, _| {},
+        )
+    }
+}
+
+/// Adds `root` and its descendants to `arena`, making each node with `make` and calling `visitor`
+/// with each new node's ID, its parent's ID, and the node it was created from (in document
+/// order); returns the root's new [`NodeId`].
+///
+/// This keeps its own stack of the nodes left to add rather than recursing, since a snapshot can
+/// be deeper than the thread's stack.
+fn add_a11y_nodes<T>(
+    root: A11yNode,
+    arena: &mut Arena<T>,
+    make: impl Fn(&A11yNode) -> T,
+    visitor: &mut impl FnMut(NodeId, Option<NodeId>, &A11yNode),
+) -> NodeId {
+    let mut root_id = None;
+    let mut stack = vec![(root, None)];
+    while let Some((mut node, parent)) = stack.pop() {
+        let id = arena.new_node(make(&node));
+        visitor(id, parent, &node);
+        match parent {
+            Some(parent) => parent.append(id, arena),
+            None => root_id = Some(id),
+        }
+        // reversed, so the first child is added next
+        let children = std::mem::take(&mut node.children);
+        stack.extend(children.into_iter().rev().map(|child| (child, Some(id))));
+    }
+    root_id.expect("The root is added first")
+}
+
+/// Tree traversal mechanisms using a backing [`Arena`] allocator.
+///
+/// Backends only need to implement the kernel: [`TreeTraversal::from_root_node`],
+/// [`TreeTraversal::build_rolesets`], [`TreeTraversal::root`], [`TreeTraversal::arena`], and
+/// [`TreeTraversal::config`] (plus [`HasRole`] on the node type).
+/// All other methods have default implementations in terms of the kernel, which backends may
+/// override when their index allows a faster answer.
+///
+/// Methods which use an index fall back to visiting nodes when the index has not been built (see
+/// [`TreeTraversal::config`]), so they always give the same result as their plain variant.
+/// Each such method has a `*_planned` variant which also returns the [`QueryPlan`] it took, e.g.,
+/// [`TreeTraversal::find_next_cached_planned`].
+pub trait TreeTraversal {
+    /// The underlying `Node` type.
+    ///
+    /// Not to be confused with [`indextree::Node`] which is a wrapper around the inner node type.
+    type Node: HasRole + Sync;
+    /// Create an index of roles starting from the leafs.
+    /// Each node will then contian a "roleset" field indicating whether _any_ descendant has a
+    /// given role.
+    /// Any rolesets already stored are replaced, so calling this again rebuilds the index.
+    ///
+    /// This will help with other traversal algorithms.
+    fn build_rolesets(&mut self);
+    /// Build a new tree arena from a pointer-based tree structure.
+    fn from_root_node(root: A11yNode) -> Self;
+    /// Returns the [`NodeId`] of the root node.
+    fn root(&self) -> NodeId;
+    /// Returns the backing [`Arena`] of all nodes.
+    fn arena(&self) -> &Arena<Self::Node>;
+    /// Returns the indexes which have been built.
+    fn config(&self) -> TreeConfig;
+    /// Returns an [`Iterator`] over all leaves in the tree.
+    fn iter_leafs(&self) -> impl Iterator<Item = &indextree::Node<Self::Node>> + use<'_, Self> {
+        let arena = self.arena();
+        self.root().descendants(arena).filter_map(|node_id| {
+            if node_id.children(arena).next().is_none() {
+                arena.get(node_id)
+            } else {
+                None
+            }
+        })
+    }
+    /// Returns a [`ParallelIterator`] over all leaves in the tree.
+    fn par_iter_leafs(
+        &self,
+    ) -> impl ParallelIterator<Item = &indextree::Node<Self::Node>> + use<'_, Self> {
+        self.arena()
+            .par_iter()
+            .filter(|node| !node.is_removed() && node.first_child().is_none())
+    }
+    /// Returns an [`Iterator`] over all nodes whose role is _not_ in `roles`, in document order.
+    fn iter_without(
+        &self,
+        roles: RoleSet,
+    ) -> impl Iterator<Item = &indextree::Node<Self::Node>> + use<'_, Self> {
+        let arena = self.arena();
+        self.root()
+            .descendants(arena)
+            .map(move |node_id| &arena[node_id])
+            .filter(move |node| !roles.contains(node.get().role().into()))
+    }
+    /// Returns an [`Iterator`] over all nodes whose role is _not_ in `roles`, in document order,
+    /// ignoring subtrees where every node has one of `roles` (i.e., the subtree's roleset is a
+    /// subset of `roles`).
+    fn iter_without_roleset(
+        &self,
+        roles: RoleSet,
+    ) -> impl Iterator<Item = &indextree::Node<Self::Node>> + use<'_, Self> {
+        if !self.config().rolesets {
+            return Either::Left(self.iter_without(roles));
+        }
+        let arena = self.arena();
+        Either::Right(
+            NodeIdExt::descendants_without(self.root(), arena, roles)
+                .map(move |node_id| &arena[node_id])
+                .filter(move |node| !roles.contains(node.get().role().into())),
+        )
+    }
+    /// Returns an [`Iterator`] over all nodes whose subtree (including the node itself) contains
+    /// _none_ of `roles`, in document order.
+    /// For example: containers with no focusable descendants.
+    /// For nodes lacking a state rather than a role, see [`Tree::iter_lacking_flags`].
+    fn iter_lacking(
+        &self,
+        roles: RoleSet,
+    ) -> impl Iterator<Item = &indextree::Node<Self::Node>> + use<'_, Self> {
+        let arena = self.arena();
+        // without rolesets, compute every subtree's roles once up front
+        let computed = (!self.config().rolesets).then(|| subtree_rolesets(arena, self.root()));
+        self.root()
+            .descendants(arena)
+            .filter(move |node_id| {
+                let subtree = match &computed {
+                    Some(computed) => computed[slot(*node_id)],
+                    None => arena[*node_id].get().roleset(),
+                };
+                !subtree.intersects(roles)
+            })
+            .map(move |node_id| &arena[node_id])
+    }
+    /// Returns the number of items with a given role.
+    fn how_many(&self, role: Role) -> usize {
+        let arena = self.arena();
+        self.root()
+            .descendants(arena)
+            .filter_map(move |node_id| arena.get(node_id))
+            .filter(|node| node.get().role() == role)
+            .count()
+    }
+    /// Returns the number of items with a given role (and avoids subtrees which do not contain the
+    /// role).
+    fn how_many_roleset(&self, role: Role) -> usize {
+        self.how_many_roleset_planned(role).0
+    }
+    /// Like [`TreeTraversal::how_many_roleset`], also returning the path it took.
+    fn how_many_roleset_planned(&self, role: Role) -> (usize, QueryPlan) {
+        self.how_many_in_planned(self.root(), role)
+    }
+    /// Returns the number of items with a given role (and computes this number in parallel).
+    fn par_how_many(&self, role: Role) -> usize {
+        self.arena()
+            .par_iter()
+            .filter(|node| !node.is_removed() && node.get().role() == role)
+            .count()
+    }
+    /// Returns the number of items with a given role (and avoids subtrees which do not contain the
+    /// role, and computes in parllel).
+    fn par_how_many_roleset(&self, role: Role) -> usize {
+        if !self.config().rolesets {
+            return self.par_how_many(role);
+        }
+        let arena = self.arena();
+        let rs: RoleSet = role.into();
+        walk_tree_prefix(self.root(), move |node_id| {
+            // children which have no descendants with a given role are ignored
+            node_id
+                .children(arena)
+                .filter(move |child| arena[*child].get().roleset().contains(rs))
+        })
+        .filter(move |node_id| arena[*node_id].get().role() == role)
+        .count()
+    }
+    /// Returns the maximum depth of the tree.
+    fn max_depth(&self) -> usize {
+        let arena = self.arena();
+        self.root()
+            .descendants(arena)
+            .map(|item| item.ancestors(arena).count())
+            .max()
+            .expect("A valid ancestors size!")
+    }
+    /// Returns the maximum depth of the tree (computes in parallel).
+    fn par_max_depth(&self) -> usize {
+        let arena = self.arena();
+        arena
+            .par_iter()
+            .filter(|node| !node.is_removed())
+            .map(|node| match node.parent() {
+                Some(parent) => parent.ancestors(arena).count(),
+                None => 0,
+            })
+            .max()
+            .expect("A valid ancestors size!")
+            + 1
+    }
+    /// Returns the unique roles in the tree (computed by visiting each node).
+    ///
+    /// Like all [`RoleSet`]s, iterating the result yields roles in ascending numeric order (see
+    /// [`RoleSet::role_iter`]); use [`TreeTraversal::unique_roles_first_occurrence`] for document
+    /// order.
+    fn unique_roles(&self) -> RoleSet {
+        let arena = self.arena();
+        self.root()
+            .descendants(arena)
+            .filter_map(move |node_id| arena.get(node_id))
+            .map(|node| node.get().role())
+            .fold(RoleSet::EMPTY, |mut roles, role| {
+                roles |= role;
+                roles
+            })
+    }
+    /// Returns the unique roles in the tree (computed by visiting each node in parallel).
+    fn par_unique_roles(&self) -> RoleSet {
+        self.arena()
+            .par_iter()
+            .filter(|node| !node.is_removed())
+            .map(|node| node.get().role())
+            // parllel fold; one `RoleSet` per core
+            .fold(
+                || RoleSet::EMPTY,
+                |mut roles, role| {
+                    roles |= role;
+                    roles
+                },
+            )
+            .reduce(|| RoleSet::EMPTY, |a, b| a | b)
+    }
+    /// Returns the unique roles in the tree (pre-computed).
+    fn unique_roles_roleset(&self) -> RoleSet {
+        self.unique_roles_in(self.root())
+    }
+    /// Returns the unique roles in the tree, sorted by their numeric value.
+    ///
+    /// This is the same order as iterating over [`TreeTraversal::unique_roles`].
+    fn unique_roles_sorted(&self) -> Vec<Role> {
+        self.unique_roles().role_iter().collect()
+    }
+    /// Returns the unique roles in the tree, in document (pre-)order of each role's first
+    /// occurrence.
+    fn unique_roles_first_occurrence(&self) -> Vec<Role> {
+        let arena = self.arena();
+        let mut seen = RoleSet::EMPTY;
+        self.root()
+            .descendants(arena)
+            .filter_map(move |node_id| arena.get(node_id))
+            .map(|node| node.get().role())
+            .filter(|role| {
+                let new = !seen.contains((*role).into());
+                seen |= *role;
+                new
+            })
+            .collect()
+    }
+    /// Returns the first in-order node with a given role.
+    fn find_first(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        let arena = self.arena();
+        self.root()
+            .descendants(arena)
+            .find_map(move |node_id| arena.get(node_id).filter(|&node| node.get().role() == role))
+    }
+    /// Like [`TreeTraversal::find_first`], also recording every node visited.
+    fn find_first_traced(&self, role: Role) -> QueryTrace {
+        let arena = self.arena();
+        let mut visited = Vec::new();
+        let found = self
+            .root()
+            .descendants(arena)
+            .inspect(|node_id| visited.push(*node_id))
+            .find(|node_id| arena[*node_id].get().role() == role);
+        QueryTrace { found, visited }
+    }
+    /// Returns the first in-order node with a given role (computes in parallel).
+    ///
+    /// This scans the arena, so it only runs in parallel while arena order is document order (see
+    /// [`TreeTraversal::arena_in_document_order`]); otherwise it falls back to
+    /// [`TreeTraversal::find_first`].
+    fn par_find_first(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        if !self.arena_in_document_order() {
+            return self.find_first(role);
+        }
+        self.arena()
+            .par_iter()
+            // instead of evenly dividing the task, exponentially increate the offset
+            // this finds earlier items sooner
+            .by_exponential_blocks()
+            .find_first(|node| !node.is_removed() && node.get().role() == role)
+    }
+    /// Returns the first in-order node with a given role, ignoring subtrees which do not contain
+    /// the role.
+    fn find_first_roleset(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        self.find_first_roleset_planned(role).0
+    }
+    /// Like [`TreeTraversal::find_first_roleset`], also returning the path it took.
+    fn find_first_roleset_planned(
+        &self,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        if !self.config().rolesets {
+            return (self.find_first(role), QueryPlan::Fallback(Index::Rolesets));
+        }
+        let arena = self.arena();
+        let found = NodeIdExt::descendants_role(self.root(), arena, role.into())
+            .find_map(move |node_id| arena.get(node_id).filter(|&node| node.get().role() == role));
+        (found, QueryPlan::Pruned)
+    }
+    /// Like [`TreeTraversal::find_first_roleset`], also recording every node visited; this is how
+    /// to check which subtrees the rolesets pruned.
+    fn find_first_roleset_traced(&self, role: Role) -> QueryTrace {
+        if !self.config().rolesets {
+            return self.find_first_traced(role);
+        }
+        let arena = self.arena();
+        let mut visited = Vec::new();
+        let found = NodeIdExt::descendants_role(self.root(), arena, role.into())
+            .inspect(|node_id| visited.push(*node_id))
+            .find(|node_id| arena[*node_id].get().role() == role);
+        QueryTrace { found, visited }
+    }
+    /// Returns the first in-order node with a given role, ignoring subtrees which do not contain
+    /// the role (computes in parallel).
+    fn par_find_first_roleset(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        if !self.config().rolesets {
+            // not `par_find_first`, which relies on arena order
+            return self.find_first(role);
+        }
+        let arena = self.arena();
+        let rs: RoleSet = role.into();
+        walk_tree_prefix(self.root(), move |node_id| {
+            // children which have no descendants with a given role are ignored
+            node_id
+                .children(arena)
+                .filter(move |child| arena[*child].get().roleset().contains(rs))
+        })
+        .map(move |node_id| &arena[node_id])
+        .find_first(|node| node.get().role() == role)
+    }
+    /// Returns the first in-order node with a given role, ignoring subtrees which do not contain
+    /// the role (computes using a stack instead of a tree walker).
+    fn find_first_stack(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        self.find_first_stack_planned(role).0
+    }
+    /// Like [`TreeTraversal::find_first_stack`], also returning the path it took.
+    fn find_first_stack_planned(
+        &self,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        if !self.config().rolesets {
+            return (self.find_first(role), QueryPlan::Fallback(Index::Rolesets));
+        }
+        let arena = self.arena();
+        let roles: RoleSet = role.into();
+        let mut stack = VecDeque::new();
+        stack.reserve(33);
+        stack.push_back(self.root());
+        while let Some(id) = stack.pop_front() {
+            let node = arena.get(id).expect("Valid ID!");
+            if node.get().role() == role {
+                return (Some(node), QueryPlan::Pruned);
+            }
+            id.children(arena)
+                .rev()
+                .filter(|child_id| arena[*child_id].get().roleset().contains(roles))
+                .for_each(|good_child| {
+                    stack.push_front(good_child);
+                });
+        }
+        (None, QueryPlan::Pruned)
+    }
+    /// Like [`TreeTraversal::find_first_stack`], also recording every node visited.
+    fn find_first_stack_traced(&self, role: Role) -> QueryTrace {
+        if !self.config().rolesets {
+            return self.find_first_traced(role);
+        }
+        let arena = self.arena();
+        let roles: RoleSet = role.into();
+        let mut visited = Vec::new();
+        let mut stack = VecDeque::new();
+        stack.push_back(self.root());
+        while let Some(id) = stack.pop_front() {
+            visited.push(id);
+            if arena[id].get().role() == role {
+                return QueryTrace {
+                    found: Some(id),
+                    visited,
+                };
+            }
+            id.children(arena)
+                .rev()
+                .filter(|child_id| arena[*child_id].get().roleset().contains(roles))
+                .for_each(|good_child| {
+                    stack.push_front(good_child);
+                });
+        }
+        QueryTrace {
+            found: None,
+            visited,
+        }
+    }
+    /// Returns the first in-order node with a given role, using a table of first occurrences
+    /// built by [`TreeTraversal::build_rolesets`] where the backend keeps one.
+    ///
+    /// By default, this is [`TreeTraversal::find_first_roleset`].
+    fn find_first_cached(&self, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        self.find_first_cached_planned(role).0
+    }
+    /// Like [`TreeTraversal::find_first_cached`], also returning the path it took.
+    ///
+    /// By default, this is [`TreeTraversal::find_first_roleset_planned`].
+    fn find_first_cached_planned(
+        &self,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        self.find_first_roleset_planned(role)
+    }
+    /// Like [`TreeTraversal::find_first_cached`], also recording every node visited; a lookup in
+    /// the table of first occurrences visits none.
+    ///
+    /// By default, this is [`TreeTraversal::find_first_roleset_traced`].
+    fn find_first_cached_traced(&self, role: Role) -> QueryTrace {
+        self.find_first_roleset_traced(role)
+    }
+    /// Returns the next node after `from` in document (pre-)order with a given role, ignoring
+    /// subtrees which do not contain the role.
+    /// Repeatedly calling this is how a screen reader moves to the next heading, link, etc.
+    ///
+    /// # Panics
+    ///
+    /// If `from` is not a valid ID in this tree.
+    fn find_next(&self, from: NodeId, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        let arena = self.arena();
+        let rs: RoleSet = role.into();
+        let rolesets = self.config().rolesets;
+        let subtree = move |node_id: NodeId| {
+            if rolesets {
+                Either::Left(NodeIdExt::descendants_role(node_id, arena, rs))
+            } else {
+                Either::Right(node_id.descendants(arena))
+            }
+        };
+        // the subtrees of each following sibling of `from`, then of each of its ancestors
+        let following = from
+            .ancestors(arena)
+            .flat_map(|anc| anc.following_siblings(arena).skip(1))
+            .filter(|sib| !rolesets || arena[*sib].get().roleset().contains(rs))
+            .flat_map(subtree);
+        subtree(from)
+            .skip(1)
+            .chain(following)
+            .find(|node_id| arena[*node_id].get().role() == role)
+            .map(|node_id| &arena[node_id])
+    }
+    /// Returns the next node after `from` in document order with a given role, using per-node
+    /// skip pointers where the backend keeps them for `role`.
+    ///
+    /// By default, this is [`TreeTraversal::find_next`].
+    ///
+    /// # Panics
+    ///
+    /// If `from` is not a valid ID in this tree.
+    fn find_next_cached(&self, from: NodeId, role: Role) -> Option<&indextree::Node<Self::Node>> {
+        self.find_next_cached_planned(from, role).0
+    }
+    /// Like [`TreeTraversal::find_next_cached`], also returning the path it took; e.g., a skip
+    /// list which is kept but still being warmed up (see [`Tree::warm_up`]) is not used.
+    ///
+    /// By default, this is [`TreeTraversal::find_next`].
+    ///
+    /// # Panics
+    ///
+    /// If `from` is not a valid ID in this tree.
+    fn find_next_cached_planned(
+        &self,
+        from: NodeId,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        (self.find_next(from, role), pruned_plan(self.config()))
+    }
+    /// Returns number of nodes in the tree.
+    fn nodes(&self) -> usize {
+        self.arena().live_count()
+    }
+    /// Returns whether the arena's live nodes are in document order, which arena scans like
+    /// [`TreeTraversal::par_find_first`] rely on.
+    ///
+    /// Backends which can add nodes after building their arena (e.g., [`Tree::append_subtree`])
+    /// must override this.
+    fn arena_in_document_order(&self) -> bool {
+        true
+    }
+    /// Returns the [`NodeId`] of a node in this tree, or `None` if the node is not from this
+    /// tree.
+    fn node_id(&self, node: &indextree::Node<Self::Node>) -> Option<NodeId> {
+        self.arena().get_node_id(node)
+    }
+    /// Returns the path from the root to `node`, as the index of each node among its siblings.
+    /// The root's path is empty.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn path(&self, node: NodeId) -> Vec<usize> {
+        let arena = self.arena();
+        let root = self.root();
+        let mut path = node
+            .ancestors(arena)
+            .take_while(|id| *id != root)
+            .map(|id| id.preceding_siblings(arena).count() - 1)
+            .collect::<Vec<_>>();
+        path.reverse();
+        path
+    }
+    /// Returns the unique roles in the subtree rooted at `node`, including `node` itself
+    /// (pre-computed).
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn unique_roles_in(&self, node: NodeId) -> RoleSet {
+        let arena = self.arena();
+        if !self.config().rolesets {
+            return node
+                .descendants(arena)
+                .fold(RoleSet::EMPTY, |roles, id| roles | arena[id].get().role());
+        }
+        arena[node].get().roleset()
+    }
+    /// Returns the number of items with a given role in the subtree rooted at `node`, including
+    /// `node` itself.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn how_many_in(&self, node: NodeId, role: Role) -> usize {
+        self.how_many_in_planned(node, role).0
+    }
+    /// Like [`TreeTraversal::how_many_in`], also returning the path it took.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn how_many_in_planned(&self, node: NodeId, role: Role) -> (usize, QueryPlan) {
+        how_many_in_traversal(self, node, role)
+    }
+    /// Returns the number of items of each role in the subtree rooted at `node`, including `node`
+    /// itself.
+    /// Roles are in ascending numeric order, and roles which do not appear are omitted.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn role_counts_in(&self, node: NodeId) -> Vec<(Role, usize)> {
+        role_counts_in_traversal(self, node)
+    }
+    /// Returns whether the subtree rooted at `node` (including `node` itself) contains _any_ of
+    /// the given roles (pre-computed).
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn subtree_contains(&self, node: NodeId, roles: RoleSet) -> bool {
+        self.unique_roles_in(node).intersects(roles)
+    }
+    /// Returns whether the subtree rooted at `node` has nothing worth announcing, according to
+    /// `policy` (pre-computed).
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    fn is_effectively_empty(&self, node: NodeId, policy: EmptinessPolicy) -> bool {
+        !self.subtree_contains(node, policy.content)
+    }
+}
+
+/// The plan of a query which ignores subtrees without the role where rolesets are built.
+fn pruned_plan(config: TreeConfig) -> QueryPlan {
+    if config.rolesets {
+        QueryPlan::Pruned
+    } else {
+        QueryPlan::Fallback(Index::Rolesets)
+    }
+}
+
+/// [`TreeTraversal::how_many_in`], ignoring subtrees which do not contain the role.
+fn how_many_in_traversal<T: TreeTraversal + ?Sized>(
+    tree: &T,
+    node: NodeId,
+    role: Role,
+) -> (usize, QueryPlan) {
+    let arena = tree.arena();
+    let plan = pruned_plan(tree.config());
+    if !tree.config().rolesets {
+        let count = node
+            .descendants(arena)
+            .filter(move |node_id| arena[*node_id].get().role() == role)
+            .count();
+        return (count, plan);
+    }
+    (count_role_in(arena, node, role), plan)
+}
+
+/// Counts `role` in the subtree rooted at `node`.
+fn count_role_in<T: HasRole>(arena: &Arena<T>, node: NodeId, role: Role) -> usize {
+    let target = RoleSet::from(role);
+    let mut count = 0;
+    // an explicit stack, since trees can be deeper than the call stack allows
+    let mut stack = vec![node];
+    while let Some(id) = stack.pop() {
+        let roles = arena[id].get().roleset();
+        if !roles.contains(target) {
+            continue;
+        }
+        // a subtree with no other roles is all `role`, so only its size is needed
+        if roles == target {
+            count += id.descendants(arena).count();
+            continue;
+        }
+        count += usize::from(arena[id].get().role() == role);
+        stack.extend(id.children(arena));
+    }
+    count
+}
+
+/// The roles in each node's subtree, by arena slot; for when rolesets have not been built.
+fn subtree_rolesets<T: HasRole>(arena: &Arena<T>, root: NodeId) -> Vec<RoleSet> {
+    let mut rolesets = vec![RoleSet::EMPTY; arena.len()];
+    // children before parents, so each node's children are complete when it is visited
+    for node_id in root
+        .descendants(arena)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        rolesets[slot(node_id)] = node_id.children(arena).fold(
+            RoleSet::from(arena[node_id].get().role()),
+            |roles, child| roles | rolesets[slot(child)],
+        );
+    }
+    rolesets
+}
+
+/// [`TreeTraversal::role_counts_in`], visiting every node in the subtree.
+fn role_counts_in_traversal<T: TreeTraversal + ?Sized>(
+    tree: &T,
+    node: NodeId,
+) -> Vec<(Role, usize)> {
+    let arena = tree.arena();
+    let mut counts = [0_usize; ROLE_BITS];
+    for node_id in node.descendants(arena) {
+        counts[arena[node_id].get().role() as usize] += 1;
+    }
+    RoleSet::ALL
+        .role_iter()
+        .map(|role| (role, counts[role as usize]))
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+/// The zero-based arena slot of a node; used to index per-node side tables.
+pub(crate) fn slot(id: NodeId) -> usize {
+    usize::from(id) - 1
+}
+
+impl TreeTraversal for TreeCount {
+    type Node = NodeCount;
+    fn build_rolesets(&mut self) {
+        self.indexed = true;
+        for leaf_id in self.root.descendants(&self.inner).collect::<Vec<_>>() {
+            let leaf_roleset = {
+                let leaf = self
+                    .inner
+                    .get_mut(leaf_id)
+                    .expect("Valid leaf node")
+                    .get_mut();
+                // descendants come later in document order, so this drops only stale counts
+                leaf.roleset = RoleSetVecCount::from_role(leaf.role);
+                leaf.role
+            };
+            // `ancestors` yields the node itself first; it was already counted above
+            for anc_id in leaf_id.ancestors(&self.inner).skip(1).collect::<Vec<_>>() {
+                let anc = self
+                    .inner
+                    .get_mut(anc_id)
+                    .expect("Valid ancestor node")
+                    .get_mut();
+                anc.roleset.add(leaf_roleset);
+            }
+        }
+    }
+    fn from_root_node(root_node: A11yNode) -> Self {
+        let mut tree: Arena<NodeCount> = Arena::new();
+        let root_id = NodeCount::from_a11y_node(root_node, &mut tree);
+        TreeCount {
+            inner: tree,
+            root: root_id,
+            indexed: false,
+        }
+    }
+    fn root(&self) -> NodeId {
+        self.root
+    }
+    fn arena(&self) -> &Arena<Self::Node> {
+        &self.inner
+    }
+    fn config(&self) -> TreeConfig {
+        TreeConfig {
+            rolesets: self.indexed,
+            counts: self.indexed,
+            ..TreeConfig::NONE
+        }
+    }
+    fn how_many_in_planned(&self, node: NodeId, role: Role) -> (usize, QueryPlan) {
+        if !self.indexed {
+            return how_many_in_traversal(self, node, role);
+        }
+        (
+            self.inner[node].get().roleset.count(role),
+            QueryPlan::Lookup(Index::Counts),
+        )
+    }
+    fn role_counts_in(&self, node: NodeId) -> Vec<(Role, usize)> {
+        if !self.indexed {
+            return role_counts_in_traversal(self, node);
+        }
+        let mut counts = self.inner[node].get().roleset.iter().collect::<Vec<_>>();
+        counts.sort_unstable_by_key(|(role, _)| *role as u32);
+        counts
+    }
+}
+
+/// A tree containing both a role, a roleset for all descendants, and the count of how many roles
+/// are in the descendants.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct TreeCount {
+    inner: Arena<NodeCount>,
+    root: NodeId,
+    /// Whether [`TreeTraversal::build_rolesets`] has been called.
+    #[serde(default)]
+    indexed: bool,
+}
+
+/// A node containing both a role, and a roleset for all descendants.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct Node {
+    /// Role of node.
+    role: Role,
+    /// Roleset of all descendants.
+    roleset: RoleSet,
+    /// Flags of the node, and of all descendants.
+    #[serde(default)]
+    flags: FlagWord,
+}
+impl HasRole for Node {
+    fn roleset(&self) -> RoleSet {
+        self.roleset
+    }
+    fn role(&self) -> Role {
+        self.role
+    }
+    fn flags(&self) -> Flags {
+        self.flags.own()
+    }
+    fn subtree_flags(&self) -> Flags {
+        self.flags.subtree()
+    }
+}
+impl Node {
+    /// Adds the created [`Node`] to a given arena; returns its new [`NodeId`].
+    pub fn from_a11y_node(node: A11yNode, tree: &mut Arena<Node>) -> NodeId {
+        Self::from_a11y_node_with_visitor(node, tree, &mut |_, _, _| {})
+    }
+    /// Adds the created [`Node`] to a given arena, calling `visitor` with each new node's ID, its
+    /// parent's ID, and the node it was created from (in document order); returns its new
+    /// [`NodeId`].
+    fn from_a11y_node_with_visitor(
+        node: A11yNode,
+        tree: &mut Arena<Node>,
+        visitor: &mut impl FnMut(NodeId, Option<NodeId>, &A11yNode),
+    ) -> NodeId {
+        add_a11y_nodes(
+            node,
+            tree,
+            |node| Node {
+                role: node.role,
+                roleset: RoleSet::default(),
+                flags: FlagWord::default(),
+            },
+            visitor,
+        )
+    }
+}
+
+/// An arena-based tree, using [`Node`] as its inner node type.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Tree {
+    /// The bit order set by [`Tree::set_role_order`]; serialized first, as a header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    order: Option<RoleOrder>,
+    /// An arena containing all [`Node`]s.
+    inner: Arena<Node>,
+    /// The [`NodeId`] for the root node.
+    root: NodeId,
+    /// The first node with each role; built with the rolesets.
+    #[serde(default)]
+    first: FirstOccurrence,
+    /// Pointers to the next node with a role, for the roles set by [`Tree::set_skip_lists`];
+    /// only the roles are serialized.
+    #[serde(default)]
+    skips: SkipLists,
+    /// Which indexes are kept; trees serialized without a config kept none.
+    #[serde(default = "no_indexes")]
+    config: TreeConfig,
+    /// The number of descendants with each role, by arena slot; empty unless
+    /// [`TreeConfig::counts`] is set.
+    #[serde(default)]
+    counts: Vec<RoleSetVecCount>,
+    /// The document order position of each node, by arena slot; built on first use if
+    /// [`TreeConfig::positions`] is set.
+    #[serde(skip)]
+    positions: OnceLock<Vec<usize>>,
+    /// The number of ancestors (including the node itself) of each node, by arena slot; empty
+    /// unless [`TreeConfig::ancestors`] is set.
+    #[serde(default)]
+    depths: Vec<usize>,
+    /// The accessible name of each node, by arena slot; empty if no names were recorded.
+    #[serde(default)]
+    names: Vec<Option<String>>,
+    /// Whether arena order is document order; unset once a subtree is appended, until the arena
+    /// is renumbered.
+    #[serde(default)]
+    arena_ordered: bool,
+}
+
+fn no_indexes() -> TreeConfig {
+    TreeConfig::NONE
+}
+
+/// Skip pointers and positions are built on first use, so whether they are built yet is not
+/// compared.
+impl PartialEq for Tree {
+    fn eq(&self, other: &Self) -> bool {
+        self.order == other.order
+            && self.inner == other.inner
+            && self.root == other.root
+            && self.first == other.first
+            && self.skips.roles() == other.skips.roles()
+            && self.config == other.config
+            && self.counts == other.counts
+            && self.depths == other.depths
+            && self.names == other.names
+            && self.arena_ordered == other.arena_ordered
+    }
+}
+impl Eq for Tree {}
+
+impl TreeTraversal for Tree {
+    type Node = Node;
+    fn build_rolesets(&mut self) {
+        self.config.rolesets = true;
+        self.first = FirstOccurrence::indexed();
+        // `descendants` is in document order, so the first node seen with a role is its first
+        // occurrence
+        for leaf_id in self.root.descendants(&self.inner).collect::<Vec<_>>() {
+            let leaf_roleset = {
+                let leaf = self
+                    .inner
+                    .get_mut(leaf_id)
+                    .expect("Valid leaf node")
+                    .get_mut();
+                // descendants come later in document order, so this drops only stale roles,
+                // e.g., of nodes removed while rolesets were dropped
+                leaf.roleset = leaf.role.into();
+                self.first.record(leaf.role, leaf_id);
+                leaf.roleset
+            };
+            for anc_id in leaf_id.ancestors(&self.inner).collect::<Vec<_>>() {
+                let anc = self
+                    .inner
+                    .get_mut(anc_id)
+                    .expect("Valid ancestor node")
+                    .get_mut();
+                anc.roleset |= leaf_roleset;
+            }
+        }
+    }
+    fn from_root_node(root_node: A11yNode) -> Self {
+        Tree::from_root_node_with_visitor(root_node, TreeConfig::NONE, |_, _, _| {})
+    }
+    fn root(&self) -> NodeId {
+        self.root
+    }
+    fn arena(&self) -> &Arena<Self::Node> {
+        &self.inner
+    }
+    fn config(&self) -> TreeConfig {
+        self.config
+    }
+    fn arena_in_document_order(&self) -> bool {
+        self.arena_ordered
+    }
+    fn find_first_cached_planned(
+        &self,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        if !self.first.is_indexed() {
+            return self.find_first_roleset_planned(role);
+        }
+        (
+            self.first.get(role).map(|id| &self.inner[id]),
+            QueryPlan::Lookup(Index::Rolesets),
+        )
+    }
+    fn find_first_cached_traced(&self, role: Role) -> QueryTrace {
+        if !self.first.is_indexed() {
+            return self.find_first_roleset_traced(role);
+        }
+        QueryTrace {
+            found: self.first.get(role),
+            visited: Vec::new(),
+        }
+    }
+    fn find_next_cached_planned(
+        &self,
+        from: NodeId,
+        role: Role,
+    ) -> (Option<&indextree::Node<Self::Node>>, QueryPlan) {
+        if !self.skips.roles().contains(role.into()) {
+            return (self.find_next(from, role), pruned_plan(self.config));
+        }
+        // while warming up, queries do not wait for pointers which are not built yet
+        if self.skips.is_warming() && !self.skips.is_built(role) {
+            return (
+                self.find_next(from, role),
+                QueryPlan::Fallback(Index::SkipList(role)),
+            );
+        }
+        let next = self
+            .skips
+            .next(&self.inner, self.root, from, role)
+            .map(|id| &self.inner[id]);
+        (next, QueryPlan::Lookup(Index::SkipList(role)))
+    }
+    fn max_depth(&self) -> usize {
+        self.root
+            .descendants(&self.inner)
+            .map(|id| self.depth(id))
+            .max()
+            .expect("A valid ancestors size!")
+    }
+    fn how_many_in_planned(&self, node: NodeId, role: Role) -> (usize, QueryPlan) {
+        match self.counts.get(slot(node)) {
+            Some(counts) => (counts.count(role), QueryPlan::Lookup(Index::Counts)),
+            None => how_many_in_traversal(self, node, role),
+        }
+    }
+    fn role_counts_in(&self, node: NodeId) -> Vec<(Role, usize)> {
+        let Some(counts) = self.counts.get(slot(node)) else {
+            return role_counts_in_traversal(self, node);
+        };
+        let mut counts = counts.iter().collect::<Vec<_>>();
+        counts.sort_unstable_by_key(|(role, _)| *role as u32);
+        counts
+    }
+}
+
+impl Tree {
+    /// Wrap an arena, without any indexes.
+    fn from_arena(inner: Arena<Node>, root: NodeId) -> Self {
+        Tree {
+            order: None,
+            inner,
+            root,
+            first: FirstOccurrence::default(),
+            skips: SkipLists::default(),
+            config: TreeConfig::NONE,
+            counts: Vec::new(),
+            positions: OnceLock::new(),
+            depths: Vec::new(),
+            names: Vec::new(),
+            arena_ordered: true,
+        }
+    }
+    /// Build a new tree arena from a pointer-based tree structure, with the indexes in `config`.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Tree, TreeConfig, TreeTraversal};
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": []}"#).unwrap();
+    /// let tree = Tree::from_root_node_with(root, TreeConfig { counts: true, ..TreeConfig::NONE });
+    /// assert!(!tree.config().rolesets);
+    /// assert_eq!(tree.how_many_in(tree.root(), atspi_common::Role::Frame), 1);
+    /// ```
+    #[must_use]
+    pub fn from_root_node_with(root_node: A11yNode, config: TreeConfig) -> Self {
+        let mut tree = Tree::from_root_node(root_node);
+        tree.config = config;
+        tree.build_indexes();
+        tree
+    }
+    /// Build a new tree arena from a pointer-based tree structure, with the indexes in `config`,
+    /// if it is within `limits`.
+    ///
+    /// ```
+    /// use a11y_tree_core::limits::Limits;
+    /// use a11y_tree_core::{Tree, TreeConfig};
+    /// # let root: a11y_tree_core::A11yNode = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Button", "children": []}
+    /// # ]}"#).unwrap();
+    /// let limits = Limits { max_nodes: 1, ..Limits::NONE };
+    /// let err = Tree::try_from_root_node_with(root, TreeConfig::default(), limits).unwrap_err();
+    /// assert_eq!(err.path, vec![0]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `root_node` exceeds `limits`; see [`limits::check`].
+    pub fn try_from_root_node_with(
+        root_node: A11yNode,
+        config: TreeConfig,
+        limits: Limits,
+    ) -> Result<Self, LimitError> {
+        limits::check(&root_node, limits)?;
+        Ok(Tree::from_root_node_with(root_node, config))
+    }
+    /// Build a new tree arena from a pointer-based tree structure, with the indexes in `config`,
+    /// calling `visitor` for each node as it is added.
+    ///
+    /// The visitor gets the node's new [`NodeId`], its parent's [`NodeId`] (or `None` for the
+    /// root), and the [`A11yNode`] it was created from; nodes are visited in document order.
+    /// This lets side tables (e.g., by name, or by object path) be built in the same pass.
+    ///
+    /// ```
+    /// use a11y_tree_core::{HasRole, Tree, TreeConfig, TreeTraversal};
+    /// use std::collections::HashMap;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Button", "name": "OK", "children": []}
+    /// # ]}"#).unwrap();
+    /// let mut by_name = HashMap::new();
+    /// let tree = Tree::from_root_node_with_visitor(root, TreeConfig::default(), |id, _, node| {
+    ///     if let Some(name) = node.name() {
+    ///         by_name.insert(name.to_string(), id);
+    ///     }
+    /// });
+    /// assert_eq!(tree.arena()[by_name["OK"]].get().role(), atspi_common::Role::Button);
+    /// ```
+    #[must_use]
+    pub fn from_root_node_with_visitor(
+        root_node: A11yNode,
+        config: TreeConfig,
+        mut visitor: impl FnMut(NodeId, Option<NodeId>, &A11yNode),
+    ) -> Self {
+        let mut arena = Arena::new();
+        let mut names = Vec::new();
+        let root =
+            Node::from_a11y_node_with_visitor(root_node, &mut arena, &mut |id, parent, node| {
+                names.push(node.name.clone());
+                visitor(id, parent, node);
+            });
+        let mut tree = Tree::from_arena(arena, root);
+        // nodes are created in document order, so slots and visits line up
+        if names.iter().any(Option::is_some) {
+            tree.names = names;
+        }
+        tree.config = config;
+        tree.build_indexes();
+        tree
+    }
+    /// Build a new tree arena from a pointer-based tree structure, with the most useful indexes
+    /// in `config` which fit within `budget` bytes; see [`TreeConfig::within_budget`].
+    ///
+    /// Returns the tree, and a report of which indexes were skipped.
+    #[must_use]
+    pub fn from_root_node_within(
+        root_node: A11yNode,
+        config: TreeConfig,
+        budget: usize,
+        profile: &UsageProfile,
+    ) -> (Self, BudgetReport) {
+        let mut tree = Tree::from_root_node(root_node);
+        let report = config.within_budget(tree.nodes(), budget, profile);
+        tree.config = report.config;
+        tree.build_indexes();
+        (tree, report)
+    }
+    /// (Re-)build every index in this tree's [`TreeConfig`].
+    pub fn build_indexes(&mut self) {
+        if self.config.rolesets {
+            self.build_rolesets();
+        }
+        self.counts.clear();
+        if self.config.counts {
+            self.counts = vec![RoleSetVecCount::default(); self.inner.len()];
+            // children before parents, so each node's children are complete when it is visited
+            let ids = self.root.descendants(&self.inner).collect::<Vec<_>>();
+            self.count_roles(&ids);
+        }
+        self.depths.clear();
+        if self.config.ancestors {
+            self.depths = vec![0; self.inner.len()];
+            let ids = self.root.descendants(&self.inner).collect::<Vec<_>>();
+            self.count_ancestors(&ids);
+        }
+        self.positions = OnceLock::new();
+        if self.config.positions {
+            self.positions();
+        }
+        self.set_skip_lists(self.config.skip_lists);
+    }
+    /// Fill in [`Tree::counts`] for `ids`, which must be in document order, and whose
+    /// descendants must either be in `ids` or already counted.
+    fn count_roles(&mut self, ids: &[NodeId]) {
+        for id in ids.iter().rev() {
+            let mut counts = RoleSetVecCount::from_role(self.inner[*id].get().role);
+            for child in id.children(&self.inner) {
+                counts.merge(&self.counts[slot(child)]);
+            }
+            self.counts[slot(*id)] = counts;
+        }
+    }
+    /// Fill in [`Tree::depths`] for `ids`, which must be in document order, and whose ancestors
+    /// must either be in `ids` or already counted.
+    fn count_ancestors(&mut self, ids: &[NodeId]) {
+        for id in ids {
+            self.depths[slot(*id)] = match self.inner[*id].parent() {
+                Some(parent) => self.depths[slot(parent)] + 1,
+                None => 1,
+            };
+        }
+    }
+    /// The document order position of each node, by arena slot.
+    fn positions(&self) -> &[usize] {
+        self.positions.get_or_init(|| {
+            let mut positions = vec![0; self.inner.len()];
+            for (i, id) in self.root.descendants(&self.inner).enumerate() {
+                positions[slot(id)] = i;
+            }
+            positions
+        })
+    }
+    /// Returns the number of ancestors of `node`, including `node` itself; the root's depth is
+    /// `1`.
+    ///
+    /// This is a lookup if [`TreeConfig::ancestors`] is set.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn depth(&self, node: NodeId) -> usize {
+        match self.depths.get(slot(node)) {
+            Some(depth) => *depth,
+            None => node.ancestors(&self.inner).count(),
+        }
+    }
+    /// Returns whether `a` comes before `b` in document (pre-)order.
+    ///
+    /// This is a lookup if [`TreeConfig::positions`] is set.
+    ///
+    /// # Panics
+    ///
+    /// If `a` or `b` is not a valid ID in this tree.
+    #[must_use]
+    pub fn precedes(&self, a: NodeId, b: NodeId) -> bool {
+        if self.config.positions {
+            let positions = self.positions();
+            return positions[slot(a)] < positions[slot(b)];
+        }
+        self.path(a) < self.path(b)
+    }
+    /// Returns the [`Flags`] set on `node`.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn flags(&self, node: NodeId) -> Flags {
+        self.inner[node].get().flags.own()
+    }
+    /// Returns whether any node in the subtree rooted at `node` (including `node` itself) has
+    /// all of `flags`; this is a lookup.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn subtree_has_flags(&self, node: NodeId, flags: Flags) -> bool {
+        self.inner[node].get().flags.subtree().contains(flags)
+    }
+    /// Sets `flags` on `node`, in addition to those already set.
+    ///
+    /// ```
+    /// use a11y_tree_core::{flags::Flags, Tree, TreeTraversal};
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Heading", "children": []}, {"role": "Heading", "children": []}
+    /// # ]}"#).unwrap();
+    /// let mut tree = Tree::from_root_node(root);
+    /// let heading = tree.root().children(tree.arena()).next().unwrap();
+    /// tree.set_flags(heading, Flags::VISITED);
+    /// assert!(tree.subtree_has_flags(tree.root(), Flags::VISITED));
+    /// assert_eq!(tree.iter_flagged(Flags::VISITED).collect::<Vec<_>>(), [heading]);
+    /// tree.clear_flags(heading, Flags::VISITED);
+    /// assert!(!tree.subtree_has_flags(tree.root(), Flags::VISITED));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    pub fn set_flags(&mut self, node: NodeId, flags: Flags) {
+        let word = self.inner[node].get().flags;
+        self.inner[node].get_mut().flags = FlagWord::new(word.own() | flags, word.subtree());
+        // stop once an ancestor already has the flags; the ones above it will too
+        for anc_id in node.ancestors(&self.inner).collect::<Vec<_>>() {
+            let word = self.inner[anc_id].get().flags;
+            if word.subtree().contains(flags) {
+                break;
+            }
+            self.inner[anc_id].get_mut().flags = FlagWord::new(word.own(), word.subtree() | flags);
+        }
+    }
+    /// Clears `flags` from `node`, leaving any others set.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    pub fn clear_flags(&mut self, node: NodeId, flags: Flags) {
+        let word = self.inner[node].get().flags;
+        self.inner[node].get_mut().flags = FlagWord::new(word.own() & !flags, word.subtree());
+        self.update_subtree_flags(node);
+    }
+    /// Recompute the subtree flags of `node` and its ancestors, after flags were removed below
+    /// them.
+    fn update_subtree_flags(&mut self, node: NodeId) {
+        // stop once an ancestor's flags are unchanged; the ones above it will be too
+        for anc_id in node.ancestors(&self.inner).collect::<Vec<_>>() {
+            let word = self.inner[anc_id].get().flags;
+            let subtree = anc_id
+                .children(&self.inner)
+                .fold(word.own(), |flags, child| {
+                    flags | self.inner[child].get().flags.subtree()
+                });
+            if subtree == word.subtree() {
+                break;
+            }
+            self.inner[anc_id].get_mut().flags = FlagWord::new(word.own(), subtree);
+        }
+    }
+    /// Returns an [`Iterator`] over the nodes with all of `flags`, in document order.
+    ///
+    /// Subtrees without such a node are skipped.
+    pub fn iter_flagged(&self, flags: Flags) -> impl Iterator<Item = NodeId> + use<'_> {
+        self.root
+            .descendants_flagged(&self.inner, flags)
+            .filter(move |id| self.inner[*id].get().flags.own().contains(flags))
+    }
+    /// Returns an [`Iterator`] over the nodes with _none_ of `flags`, in document order; e.g., the
+    /// nodes which have not been read yet.
+    ///
+    /// Every node is visited: subtree flags only record whether _any_ node has a flag, so no
+    /// subtree can be skipped.
+    pub fn iter_without_flags(&self, flags: Flags) -> impl Iterator<Item = NodeId> + use<'_> {
+        self.root
+            .descendants(&self.inner)
+            .filter(move |id| !self.inner[*id].get().flags.own().intersects(flags))
+    }
+    /// Returns an [`Iterator`] over the nodes whose subtree (including the node itself) has
+    /// _none_ of `flags`, in document order; e.g., regions where nothing has been read yet.
+    ///
+    /// This is the flags counterpart of [`TreeTraversal::iter_lacking`].
+    pub fn iter_lacking_flags(&self, flags: Flags) -> impl Iterator<Item = NodeId> + use<'_> {
+        self.root
+            .descendants(&self.inner)
+            .filter(move |id| !self.inner[*id].get().flags.subtree().intersects(flags))
+    }
+    /// Returns the accessible name of `node`, if it was recorded.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn name(&self, node: NodeId) -> Option<&str> {
+        assert!(!node.is_removed(&self.inner), "Node is in this tree");
+        self.names.get(slot(node))?.as_deref()
+    }
+    /// Returns the text of the leaves under `node` (or of `node` itself, if it is a leaf), in
+    /// reading order; i.e., what a screen reader announces for a whole container.
+    ///
+    /// Leaves without a recorded name are skipped.
+    /// Text is separated by a space, or by [`TextPolicy`]'s separators when a block or cell
+    /// starts or ends between two leaves.
+    ///
+    /// ```
+    /// use a11y_tree_core::{policy::TextPolicy, Tree, TreeTraversal};
+    /// # let root = serde_json::from_str(r#"{"role": "Section", "children": [
+    /// #     {"role": "Paragraph", "children": [
+    /// #         {"role": "Static", "name": "Read the", "children": []},
+    /// #         {"role": "Link", "name": "docs", "children": []}
+    /// #     ]},
+    /// #     {"role": "Table", "children": [{"role": "TableRow", "children": [
+    /// #         {"role": "TableCell", "name": "A", "children": []},
+    /// #         {"role": "TableCell", "name": "B", "children": []}
+    /// #     ]}]}
+    /// # ]}"#).unwrap();
+    /// let tree = Tree::from_root_node(root);
+    /// let text = tree.flatten_text(tree.root(), &TextPolicy::default());
+    /// assert_eq!(text, "Read the docs\nA\tB");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree.
+    #[must_use]
+    pub fn flatten_text(&self, node: NodeId, policy: &TextPolicy) -> String {
+        let mut text = String::new();
+        let mut pending = Break::Word;
+        for edge in node.traverse(&self.inner) {
+            let (NodeEdge::Start(id) | NodeEdge::End(id)) = edge;
+            pending = pending.max(policy.break_at(self.inner[id].get().role));
+            let is_leaf = self.inner[id].first_child().is_none();
+            let (NodeEdge::Start(_), true, Some(name)) = (edge, is_leaf, self.name(id)) else {
+                continue;
+            };
+            if !text.is_empty() {
+                text.push_str(policy.separator(pending));
+            }
+            text.push_str(name);
+            pending = Break::Word;
+        }
+        text
+    }
+    /// Returns a copy of this tree with only the nodes with one of `roles`, their ancestors, and
+    /// the root; the copy has the same [`TreeConfig`].
+    ///
+    /// Kept nodes keep their relative order and nesting, so e.g. a heading inside a section is
+    /// still inside it in the projection.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Tree, TreeConfig, TreeTraversal};
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Section", "children": [{"role": "Heading", "children": []}]},
+    /// #     {"role": "Paragraph", "children": []}
+    /// # ]}"#).unwrap();
+    /// let tree = Tree::from_root_node_with(root, TreeConfig::default());
+    /// let headings = tree.project(Role::Heading.into());
+    /// assert_eq!(headings.nodes(), 3);
+    /// assert_eq!(headings.how_many_roleset(Role::Heading), 1);
+    /// assert_eq!(headings.how_many_roleset(Role::Paragraph), 0);
+    /// ```
+    #[must_use]
+    pub fn project(&self, roles: RoleSet) -> Tree {
+        let root = self
+            .project_node(self.root, roles)
+            .unwrap_or_else(|| A11yNode {
+                role: self.inner[self.root].get().role,
+                name: self.name(self.root).map(String::from),
+                toolkit: None,
+                children: Vec::new(),
+            });
+        Tree::from_root_node_with(root, self.config)
+    }
+    /// The subtree rooted at `node`, with only nodes with one of `roles` and their ancestors; or
+    /// `None` if there are no such nodes.
+    fn project_node(&self, node: NodeId, roles: RoleSet) -> Option<A11yNode> {
+        let children = node
+            .children(&self.inner)
+            .filter_map(|child| self.project_node(child, roles))
+            .collect::<Vec<_>>();
+        let role = self.inner[node].get().role;
+        if children.is_empty() && !roles.intersects(role.into()) {
+            return None;
+        }
+        Some(A11yNode {
+            role,
+            name: self.name(node).map(String::from),
+            toolkit: None,
+            children,
+        })
+    }
+    /// The bit order for this tree's roles: the one set by [`Tree::set_role_order`], or else
+    /// the most common roles first.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Tree, TreeTraversal};
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Link", "children": []}, {"role": "Link", "children": []}
+    /// # ]}"#).unwrap();
+    /// let mut tree = Tree::from_root_node(root);
+    /// let order = tree.role_order();
+    /// assert_eq!(&order.roles()[..2], [Role::Link, Role::Frame]);
+    /// tree.set_role_order(Some(order.clone()));
+    /// let json = serde_json::to_string(&tree).unwrap();
+    /// assert_eq!(serde_json::from_str::<Tree>(&json).unwrap().role_order(), order);
+    /// ```
+    #[must_use]
+    pub fn role_order(&self) -> RoleOrder {
+        self.order
+            .clone()
+            .unwrap_or_else(|| RoleOrder::by_frequency(&self.role_counts_in(self.root)))
+    }
+    /// Keep `order` in this tree's serialized header; it is not updated when the tree changes.
+    pub fn set_role_order(&mut self, order: Option<RoleOrder>) {
+        self.order = order;
+    }
+    /// Keep skip pointers for `roles`, making [`TreeTraversal::find_next_cached`] a lookup for
+    /// those roles.
+    /// Any previously built pointers are dropped.
+    ///
+    /// Pointers for a role are built on its first query, and cost one pointer per node; see
+    /// [`Tree::skip_list_bytes`].
+    pub fn set_skip_lists(&mut self, roles: RoleSet) {
+        self.config.skip_lists = roles;
+        self.skips = SkipLists::new(roles);
+    }
+    /// Builds skip pointers for `roles` (adding any which are not kept yet) and, if
+    /// [`TreeConfig::positions`] is set, positions, on the [`rayon`] thread pool; then calls
+    /// `on_done` with the roles whose pointers were built.
+    ///
+    /// Until a role's pointers are built, [`TreeTraversal::find_next_cached`] falls back to
+    /// [`TreeTraversal::find_next`] for it, so queries can be served straight away.
+    /// The write lock is only held to add the roles; each role is built under its own read
+    /// lock, so writes are not held up for the whole warm-up.
+    /// A write during the warm-up drops the pointers built so far, which are then rebuilt on their
+    /// next query; roles whose pointers are dropped from the config during the warm-up are
+    /// skipped.
+    ///
+    /// ```
+    /// use a11y_tree_core::{RoleSet, Tree, TreeTraversal};
+    /// use atspi_common::Role;
+    /// use std::sync::{mpsc, Arc, RwLock};
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Heading", "children": []}
+    /// # ]}"#).unwrap();
+    /// let tree = Arc::new(RwLock::new(Tree::from_root_node(root)));
+    /// let (tx, rx) = mpsc::channel();
+    /// Tree::warm_up(&tree, RoleSet::EMPTY | Role::Heading | Role::Link, move |built| {
+    ///     tx.send(built).unwrap();
+    /// });
+    /// // queries are answered while the warm-up runs
+    /// let t = tree.read().unwrap();
+    /// assert!(t.find_next_cached(t.root(), Role::Heading).is_some());
+    /// # drop(t);
+    /// assert_eq!(rx.recv().unwrap(), RoleSet::EMPTY | Role::Heading | Role::Link);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned; the spawned task panics if it is poisoned later.
+    pub fn warm_up(
+        tree: &Arc<RwLock<Tree>>,
+        roles: RoleSet,
+        on_done: impl FnOnce(RoleSet) + Send + 'static,
+    ) {
+        {
+            let mut t = tree.write().expect("Lock is not poisoned");
+            t.config.skip_lists |= roles;
+            t.skips.add_roles(roles);
+            t.skips.set_warming(true);
+        }
+        let tree = Arc::clone(tree);
+        rayon::spawn(move || {
+            for role in roles.role_iter() {
+                let t = tree.read().expect("Lock is not poisoned");
+                // the role may have been dropped since (e.g., by `set_skip_lists` or `compact`)
+                if !t.skips.roles().contains(role.into()) {
+                    continue;
+                }
+                t.skips.warm(&t.inner, t.root, role);
+            }
+            let t = tree.read().expect("Lock is not poisoned");
+            if t.config.positions {
+                t.positions();
+            }
+            t.skips.set_warming(false);
+            let built = roles
+                .role_iter()
+                .filter(|role| t.skips.is_built(*role))
+                .fold(RoleSet::EMPTY, |built, role| built | role);
+            drop(t);
+            on_done(built);
+        });
+    }
+    /// The roles which skip pointers are kept for.
+    #[must_use]
+    pub fn skip_list_roles(&self) -> RoleSet {
+        self.skips.roles()
+    }
+    /// Returns the memory used by the skip pointers built so far, in bytes.
+    #[must_use]
+    pub fn skip_list_bytes(&self) -> usize {
+        self.skips.bytes()
+    }
+    /// Appends `node` (and its children) as the last child of `parent`; returns the new node's
+    /// [`NodeId`].
+    ///
+    /// Every index in the tree's [`TreeConfig`] is updated, so the tree does not need to be
+    /// re-indexed.
+    /// Skip pointers and positions are rebuilt on their next query.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not a valid ID in this tree.
+    pub fn append_subtree(&mut self, parent: NodeId, node: A11yNode) -> NodeId {
+        self.skips.invalidate();
+        self.positions.take();
+        self.arena_ordered = false;
+        let mut names = Vec::new();
+        let id = Node::from_a11y_node_with_visitor(node, &mut self.inner, &mut |id, _, node| {
+            names.push((id, node.name.clone()));
+        });
+        parent.append(id, &mut self.inner);
+        if !self.names.is_empty() || names.iter().any(|(_, name)| name.is_some()) {
+            // slots of removed nodes may be reused, so every new node's name is set
+            self.names.resize(self.inner.len(), None);
+            for (node_id, name) in names {
+                self.names[slot(node_id)] = name;
+            }
+        }
+        let new_ids = id.descendants(&self.inner).collect::<Vec<_>>();
+        if self.config.counts {
+            self.counts
+                .resize(self.inner.len(), RoleSetVecCount::default());
+            self.count_roles(&new_ids);
+            let added = self.counts[slot(id)].clone();
+            for anc_id in parent.ancestors(&self.inner).collect::<Vec<_>>() {
+                self.counts[slot(anc_id)].merge(&added);
+            }
+        }
+        if self.config.ancestors {
+            self.depths.resize(self.inner.len(), 0);
+            self.count_ancestors(&new_ids);
+        }
+        if !self.config.rolesets {
+            return id;
+        }
+        // children before parents, so each node's children are complete when it is visited
+        for node_id in new_ids.iter().rev() {
+            let roleset = node_id.children(&self.inner).fold(
+                RoleSet::from(self.inner[*node_id].get().role),
+                |rs, child| rs | self.inner[child].get().roleset,
+            );
+            self.inner[*node_id].get_mut().roleset = roleset;
+        }
+        let added = self.inner[id].get().roleset;
+        for anc_id in parent.ancestors(&self.inner).collect::<Vec<_>>() {
+            self.inner[anc_id].get_mut().roleset |= added;
+        }
+        if self.first.is_indexed() {
+            for node_id in new_ids {
+                let role = self.inner[node_id].get().role;
+                // the new subtree may come before the previous first occurrence, if it was
+                // appended to one of that node's ancestors
+                let is_first = self
+                    .first
+                    .get(role)
+                    .is_none_or(|first| self.precedes(node_id, first));
+                if is_first {
+                    self.first.set(role, Some(node_id));
+                }
+            }
+        }
+        id
+    }
+    /// Removes `node` and all its descendants.
+    ///
+    /// Every index in the tree's [`TreeConfig`] is updated, so the tree does not need to be
+    /// re-indexed.
+    /// Skip pointers and positions are rebuilt on their next query.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in this tree, or is the root.
+    pub fn remove_subtree(&mut self, node: NodeId) {
+        assert_ne!(node, self.root, "Cannot remove the root node");
+        let removed = self.inner[node].get().roleset;
+        let parent = self.inner[node]
+            .parent()
+            .expect("Non-root node has a parent");
+        let stale = removed
+            .role_iter()
+            .filter(|role| {
+                self.first
+                    .get(*role)
+                    .is_some_and(|first| first.ancestors(&self.inner).any(|id| id == node))
+            })
+            .collect::<Vec<_>>();
+        self.skips.invalidate();
+        self.positions.take();
+        if self.config.counts {
+            let removed = self.counts[slot(node)].clone();
+            for anc_id in parent.ancestors(&self.inner).collect::<Vec<_>>() {
+                self.counts[slot(anc_id)].subtract(&removed);
+            }
+        }
+        node.remove_subtree(&mut self.inner);
+        self.update_subtree_flags(parent);
+        if !self.config.rolesets {
+            return;
+        }
+        // stop once an ancestor's roleset is unchanged; the ones above it will be too
+        for anc_id in parent.ancestors(&self.inner).collect::<Vec<_>>() {
+            let roleset = anc_id
+                .children(&self.inner)
+                .fold(RoleSet::from(self.inner[anc_id].get().role), |rs, child| {
+                    rs | self.inner[child].get().roleset
+                });
+            if roleset == self.inner[anc_id].get().roleset {
+                break;
+            }
+            self.inner[anc_id].get_mut().roleset = roleset;
+        }
+        for role in stale {
+            let first = self
+                .find_first_roleset(role)
+                .and_then(|found| self.inner.get_node_id(found));
+            self.first.set(role, first);
+        }
+    }
+}
+
+/// A node in a tree. The standard type which [`Tree`] and [`TreeCount`] use to create their
+/// arena-based trees.
+///
+/// TODO: should also be tested in benchmarks for comparison.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct A11yNode {
+    /// The role of the node.
+    role: Role,
+    /// The accessible name of the node, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// The toolkit (e.g., `GTK`) of an application's root node, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    toolkit: Option<String>,
+    /// The children of the node.
+    children: Vec<A11yNode>,
+}
+
+impl Drop for A11yNode {
+    /// Drops the descendants one at a time, rather than recursing, since a snapshot can be deeper
+    /// than the thread's stack.
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CharSet {
+    pub horizontal: char,
+    pub vertical: char,
+    pub connector: char,
+    pub end_connector: char,
+}
+/// Defenition of formatting characters for pretty-printing [`A11yNode`].
+const SINGLE_LINE: CharSet = CharSet {
+    horizontal: '─',
+    vertical: '│',
+    connector: '├',
+    end_connector: '└',
+};
+
+impl A11yNode {
+    /// The role of the node.
+    #[must_use]
+    pub fn role(&self) -> Role {
+        self.role
+    }
+    /// The accessible name of the node, if it was recorded.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// The toolkit of an application's root node, if it was recorded.
+    #[must_use]
+    pub fn toolkit(&self) -> Option<&str> {
+        self.toolkit.as_deref()
+    }
+    /// The children of the node.
+    #[must_use]
+    pub fn children(&self) -> &[A11yNode] {
+        &self.children
+    }
+}
+
+#[cfg(not(coverage))]
+impl Display for A11yNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, SINGLE_LINE, &mut Vec::new())
+    }
+}
+
+#[cfg(not(coverage))]
+impl A11yNode {
+    fn fmt_with(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        style: CharSet,
+        prefix: &mut Vec<bool>,
+    ) -> std::fmt::Result {
+        let mut stack: Vec<(&Self, usize, usize)> = vec![(self, 0, 0)];
+        while let Some((this, siblings, idx)) = stack.pop() {
+            if siblings > 0 {
+                prefix.push(idx == siblings - 1);
+            }
+            for (i, is_last_at_i) in prefix.iter().enumerate() {
+                // if it is the last portion of the line
+                let is_last = i == prefix.len() - 1;
+                match (is_last, *is_last_at_i) {
+                    (true, true) => write!(f, "{}", style.end_connector)?,
+                    (true, false) => write!(f, "{}", style.connector)?,
+                    // four spaces to emulate `tree`
+                    (false, true) => write!(f, "    ")?,
+                    // three spaces and vertical char
+                    (false, false) => write!(f, "{}   ", style.vertical)?,
+                }
+            }
+
+            // two horizontal chars to mimic `tree`
+            writeln!(
+                f,
+                "{}{} {}({})",
+                style.horizontal,
+                style.horizontal,
+                this.role,
+                this.children.len()
+            )?;
+
+            for (i, child) in this.children.iter().enumerate() {
+                stack.push((child, this.children.len(), i));
+            }
+            if this.children.is_empty() {
+                continue;
+            }
+            prefix.pop();
+        }
+        Ok(())
+    }
+}
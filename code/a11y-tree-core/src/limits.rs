@@ -0,0 +1,346 @@
+//! Limits on the size of snapshots, checked while they are read and before they are built.
+//!
+//! A hostile or broken application can export an absurd tree; a screen reader should refuse it
+//! rather than run out of memory.
+//! [`from_json_str`] stops reading as soon as a limit is exceeded, and
+//! [`Tree::try_from_root_node_with`](crate::Tree::try_from_root_node_with) checks an existing
+//! snapshot before building any arena.
+
+use crate::{A11yNode, Node};
+use atspi_common::Role;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The estimated bytes used by each node: its snapshot node, and its node in the arena.
+/// Names are counted separately, by their length.
+pub const NODE_BYTES: usize = size_of::<A11yNode>() + size_of::<indextree::Node<Node>>();
+
+/// The largest snapshot to accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most nodes, including the root.
+    pub max_nodes: usize,
+    /// The most estimated bytes; see [`NODE_BYTES`].
+    pub max_bytes: usize,
+    /// The most nodes on the path from the root to any node, including both (as in
+    /// [`Tree::depth`](crate::Tree::depth)).
+    pub max_depth: usize,
+}
+
+impl Limits {
+    /// No limits.
+    pub const NONE: Limits = Limits {
+        max_nodes: usize::MAX,
+        max_bytes: usize::MAX,
+        max_depth: usize::MAX,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::NONE
+    }
+}
+
+/// Which limit a snapshot exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exceeded {
+    /// [`Limits::max_nodes`].
+    Nodes(usize),
+    /// [`Limits::max_bytes`].
+    Bytes(usize),
+    /// [`Limits::max_depth`].
+    Depth(usize),
+}
+
+impl Display for Exceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::Nodes(max) => write!(f, "snapshot has more than {max} nodes"),
+            Exceeded::Bytes(max) => write!(f, "snapshot needs more than {max} bytes"),
+            Exceeded::Depth(max) => write!(f, "snapshot is more than {max} nodes deep"),
+        }
+    }
+}
+
+/// A snapshot exceeded its [`Limits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitError {
+    /// The limit which was exceeded.
+    pub exceeded: Exceeded,
+    /// The path (child indexes from the root) of the node at which it was exceeded.
+    pub path: Vec<usize>,
+}
+
+impl LimitError {
+    fn at(exceeded: Exceeded, path: &[usize]) -> Self {
+        LimitError {
+            exceeded,
+            path: path.to_vec(),
+        }
+    }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (reached at path {:?})", self.exceeded, self.path)
+    }
+}
+
+impl Error for LimitError {}
+
+/// A running total of nodes and bytes, checked against [`Limits`] as each node is added.
+pub(crate) struct Guard {
+    limits: Limits,
+    nodes: Cell<usize>,
+    bytes: Cell<usize>,
+}
+
+impl Guard {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Guard {
+            limits,
+            nodes: Cell::new(0),
+            bytes: Cell::new(0),
+        }
+    }
+    /// Count the node at `path`.
+    pub(crate) fn add_node(&self, path: &[usize]) -> Result<(), LimitError> {
+        // the path does not include the root, so its length is the node's depth less one
+        if path.len() >= self.limits.max_depth {
+            return Err(LimitError::at(Exceeded::Depth(self.limits.max_depth), path));
+        }
+        self.nodes.set(self.nodes.get().saturating_add(1));
+        if self.nodes.get() > self.limits.max_nodes {
+            return Err(LimitError::at(Exceeded::Nodes(self.limits.max_nodes), path));
+        }
+        self.add_bytes(NODE_BYTES, path)
+    }
+    /// Count `bytes` more, used by the node at `path`.
+    pub(crate) fn add_bytes(&self, bytes: usize, path: &[usize]) -> Result<(), LimitError> {
+        self.bytes.set(self.bytes.get().saturating_add(bytes));
+        if self.bytes.get() > self.limits.max_bytes {
+            return Err(LimitError::at(Exceeded::Bytes(self.limits.max_bytes), path));
+        }
+        Ok(())
+    }
+    /// Count the node at `path` and its name.
+    fn check_node(&self, node: &A11yNode, path: &[usize]) -> Result<(), LimitError> {
+        self.add_node(path)?;
+        self.add_bytes(node.name.as_ref().map_or(0, String::len), path)
+    }
+    /// Check `root` and its descendants, in document order.
+    ///
+    /// This keeps its own stack of the children left to check rather than recursing, since the
+    /// snapshot may be deeper than the thread's stack.
+    fn check(&self, root: &A11yNode) -> Result<(), LimitError> {
+        let mut path = Vec::new();
+        self.check_node(root, &path)?;
+        let mut stack = vec![root.children.iter().enumerate()];
+        while let Some(children) = stack.last_mut() {
+            if let Some((i, child)) = children.next() {
+                path.push(i);
+                self.check_node(child, &path)?;
+                stack.push(child.children.iter().enumerate());
+            } else {
+                // every child is checked, so the path leaves their parent
+                stack.pop();
+                path.pop();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that `root` is within `limits`.
+///
+/// # Errors
+///
+/// If it is not; the error's path is that of the first node, in document order, at which a limit
+/// was exceeded.
+pub fn check(root: &A11yNode, limits: Limits) -> Result<(), LimitError> {
+    Guard::new(limits).check(root)
+}
+
+/// Read a snapshot from JSON, stopping as soon as it exceeds `limits`.
+///
+/// ```
+/// use a11y_tree_core::limits::{self, Limits};
+/// let json = r#"{"role": "List", "children": [
+///     {"role": "ListItem", "children": []},
+///     {"role": "ListItem", "children": []}
+/// ]}"#;
+/// assert!(limits::from_json_str(json, Limits { max_nodes: 3, ..Limits::NONE }).is_ok());
+/// let err = limits::from_json_str(json, Limits { max_nodes: 2, ..Limits::NONE }).unwrap_err();
+/// assert!(err.to_string().contains("at path [1]"));
+/// ```
+///
+/// # Errors
+///
+/// If `json` is not a valid snapshot, or exceeds `limits`; the message includes the path of the
+/// node at which the limit was exceeded.
+pub fn from_json_str(json: &str, limits: Limits) -> serde_json::Result<A11yNode> {
+    let guard = Guard::new(limits);
+    let mut de = serde_json::Deserializer::from_str(json);
+    let node = NodeSeed {
+        guard: &guard,
+        path: Vec::new(),
+    }
+    .deserialize(&mut de)?;
+    de.end()?;
+    Ok(node)
+}
+
+/// Deserializes one node at `path`, counting it (and its descendants) against the guard.
+struct NodeSeed<'a> {
+    guard: &'a Guard,
+    path: Vec<usize>,
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_> {
+    type Value = A11yNode;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<A11yNode, D::Error> {
+        deserializer.deserialize_struct("A11yNode", &["role", "name", "toolkit", "children"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_> {
+    type Value = A11yNode;
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("an A11yNode")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<A11yNode, A::Error> {
+        self.guard.add_node(&self.path).map_err(de::Error::custom)?;
+        let mut role: Option<Role> = None;
+        let mut name: Option<String> = None;
+        let mut toolkit: Option<String> = None;
+        let mut children = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "role" => role = Some(map.next_value()?),
+                "name" => {
+                    name = map.next_value()?;
+                    let len = name.as_ref().map_or(0, String::len);
+                    self.guard
+                        .add_bytes(len, &self.path)
+                        .map_err(de::Error::custom)?;
+                }
+                "toolkit" => {
+                    toolkit = map.next_value()?;
+                    let len = toolkit.as_ref().map_or(0, String::len);
+                    self.guard
+                        .add_bytes(len, &self.path)
+                        .map_err(de::Error::custom)?;
+                }
+                "children" => {
+                    children = Some(map.next_value_seed(ChildrenSeed {
+                        guard: self.guard,
+                        path: &self.path,
+                    })?);
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(A11yNode {
+            role: role.ok_or_else(|| de::Error::missing_field("role"))?,
+            name,
+            toolkit,
+            children: children.ok_or_else(|| de::Error::missing_field("children"))?,
+        })
+    }
+}
+
+/// Deserializes the children of the node at `path`.
+struct ChildrenSeed<'a> {
+    guard: &'a Guard,
+    path: &'a [usize],
+}
+
+impl<'de> DeserializeSeed<'de> for ChildrenSeed<'_> {
+    type Value = Vec<A11yNode>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<A11yNode>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildrenSeed<'_> {
+    type Value = Vec<A11yNode>;
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of A11yNodes")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<A11yNode>, A::Error> {
+        let mut children = Vec::new();
+        loop {
+            let mut path = self.path.to_vec();
+            path.push(children.len());
+            let seed = NodeSeed {
+                guard: self.guard,
+                path,
+            };
+            match seq.next_element_seed(seed)? {
+                Some(child) => children.push(child),
+                None => return Ok(children),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, from_json_str, Exceeded, Limits, NODE_BYTES};
+    use crate::A11yNode;
+
+    const JSON: &str = r#"{"role": "Frame", "children": [
+        {"role": "Panel", "name": "Side", "children": [
+            {"role": "Label", "name": "Hello", "children": []}
+        ]},
+        {"role": "Button", "name": "OK", "children": []}
+    ]}"#;
+
+    #[test]
+    fn limits() {
+        let expected: A11yNode = serde_json::from_str(JSON).expect("Valid JSON");
+        assert_eq!(
+            from_json_str(JSON, Limits::NONE).expect("Valid JSON"),
+            expected
+        );
+        assert_eq!(check(&expected, Limits::NONE), Ok(()));
+        let nodes = Limits {
+            max_nodes: 2,
+            ..Limits::NONE
+        };
+        let err = check(&expected, nodes).expect_err("Too many nodes");
+        assert_eq!(err.exceeded, Exceeded::Nodes(2));
+        assert_eq!(err.path, vec![0, 0]);
+        assert!(from_json_str(JSON, nodes)
+            .expect_err("Too many nodes")
+            .to_string()
+            .contains(&err.to_string()));
+        // every node fits, but not every name
+        let bytes = Limits {
+            max_bytes: 4 * NODE_BYTES + "SideHello".len(),
+            ..Limits::NONE
+        };
+        let err = check(&expected, bytes).expect_err("Too many bytes");
+        assert_eq!(err.path, vec![1]);
+        assert!(from_json_str(JSON, bytes)
+            .expect_err("Too many bytes")
+            .to_string()
+            .contains(&err.to_string()));
+        let depth = Limits {
+            max_depth: 2,
+            ..Limits::NONE
+        };
+        let err = check(&expected, depth).expect_err("Too deep");
+        assert_eq!(err.exceeded, Exceeded::Depth(2));
+        assert_eq!(err.path, vec![0, 0]);
+        assert!(from_json_str(JSON, depth)
+            .expect_err("Too deep")
+            .to_string()
+            .contains(&err.to_string()));
+    }
+}
@@ -0,0 +1,340 @@
+//! Reclaiming the memory of a tree which is kept, but no longer queried much.
+//!
+//! A screen reader may keep the snapshot of a huge document after the user has moved on, in case
+//! they come back to it.
+//! [`Tree::drop_index`] frees indexes which are no longer worth their memory,
+//! [`Tree::shrink_to_fit`] frees the slots of removed nodes and any spare capacity, and
+//! [`Tree::compact_in_background`] does both from an idle-time hook, without dropping the tree's
+//! structure.
+
+use crate::{
+    slot, FirstOccurrence, Index, Node, RoleSet, RoleSetVecCount, Tree, TreeConfig, UsageProfile,
+};
+use indextree::{Arena, NodeId};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// The memory used by a tree before and after [`Tree::compact`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reclaimed {
+    /// [`Tree::heap_bytes`] before compacting.
+    pub before: usize,
+    /// [`Tree::heap_bytes`] after compacting.
+    pub after: usize,
+    /// Whether the arena was rebuilt, which invalidates every [`NodeId`] from before.
+    pub renumbered: bool,
+}
+
+/// Moves each entry of an index kept by arena slot to the new slot of its node; `ids` is the new
+/// [`NodeId`] of each node by old slot, and `live` the number of nodes.
+fn remap_slots<T: Default>(index: &mut Vec<T>, ids: &[Option<NodeId>], live: usize) {
+    if index.is_empty() {
+        return;
+    }
+    let mut remapped = Vec::with_capacity(live);
+    remapped.resize_with(live, T::default);
+    for (entry, id) in index.iter_mut().zip(ids) {
+        if let Some(id) = id {
+            remapped[slot(*id)] = std::mem::take(entry);
+        }
+    }
+    *index = remapped;
+}
+
+impl Tree {
+    /// Returns the memory used by the tree's nodes, names and indexes, in bytes (excluding the
+    /// [`Tree`] itself).
+    /// The table of first occurrences is estimated as in [`Index::bytes`].
+    #[must_use]
+    pub fn heap_bytes(&self) -> usize {
+        let arena = self.inner.capacity() * size_of::<indextree::Node<Node>>();
+        let first = if self.first.is_indexed() {
+            Index::Rolesets.bytes(0)
+        } else {
+            0
+        };
+        let counts = self.counts.capacity() * size_of::<RoleSetVecCount>()
+            + self
+                .counts
+                .iter()
+                .map(RoleSetVecCount::heap_bytes)
+                .sum::<usize>();
+        let positions = self
+            .positions
+            .get()
+            .map_or(0, |positions| positions.capacity() * size_of::<usize>());
+        let depths = self.depths.capacity() * size_of::<usize>();
+        let names = self.names.capacity() * size_of::<Option<String>>()
+            + self
+                .names
+                .iter()
+                .flatten()
+                .map(String::capacity)
+                .sum::<usize>();
+        arena + first + counts + positions + depths + names + self.skip_list_bytes()
+    }
+    /// Drops `index` and stops keeping it up to date, as if it had never been in the tree's
+    /// [`TreeConfig`]; queries which used it fall back as they would without it (see
+    /// [`crate::QueryPlan`]).
+    /// It can be rebuilt with [`Tree::build_indexes`] after setting it in the config again.
+    ///
+    /// Rolesets are stored in every node, so dropping them only frees the table of first
+    /// occurrences.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Index, Tree, TreeConfig, TreeTraversal};
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Link", "children": []}
+    /// # ]}"#).unwrap();
+    /// let mut tree = Tree::from_root_node_with(root, TreeConfig::ALL);
+    /// tree.drop_index(Index::Counts);
+    /// assert!(!tree.config().counts);
+    /// assert_eq!(tree.how_many_roleset(Role::Link), 1);
+    /// ```
+    pub fn drop_index(&mut self, index: Index) {
+        match index {
+            Index::Rolesets => {
+                self.config.rolesets = false;
+                self.first = FirstOccurrence::default();
+            }
+            Index::Counts => {
+                self.config.counts = false;
+                self.counts = Vec::new();
+            }
+            Index::Positions => {
+                self.config.positions = false;
+                self.positions = OnceLock::new();
+            }
+            Index::SkipList(role) => {
+                self.config.skip_lists &= !RoleSet::from(role);
+                self.skips.remove(role.into());
+            }
+            Index::Ancestors => {
+                self.config.ancestors = false;
+                self.depths = Vec::new();
+            }
+        }
+    }
+    /// Frees the arena slots of removed nodes, and any spare capacity in the arena, names and
+    /// indexes.
+    ///
+    /// If any nodes were removed, the arena is rebuilt with the nodes renumbered in document
+    /// order, so every [`NodeId`] from before is invalid; returns whether that happened.
+    /// Indexes are moved to the new slots rather than rebuilt, except skip pointers and
+    /// positions, which are rebuilt on their next query.
+    pub fn shrink_to_fit(&mut self) -> bool {
+        let renumbered = self.inner.len() > self.inner.live_count();
+        if renumbered {
+            self.renumber();
+        }
+        self.inner.shrink_to_fit();
+        self.counts.shrink_to_fit();
+        for counts in &mut self.counts {
+            counts.shrink_to_fit();
+        }
+        self.depths.shrink_to_fit();
+        self.names.shrink_to_fit();
+        renumbered
+    }
+    /// Rebuilds the arena with only the live nodes, in document order.
+    fn renumber(&mut self) {
+        let live = self.inner.live_count();
+        let mut inner = Arena::with_capacity(live);
+        // the new ID of each node, by old arena slot; parents come before their children
+        let mut ids: Vec<Option<NodeId>> = vec![None; self.inner.len()];
+        for old in self.root.descendants(&self.inner) {
+            let node = self.inner[old].get();
+            let new = inner.new_node(Node {
+                role: node.role,
+                roleset: node.roleset,
+                flags: node.flags,
+            });
+            if let Some(parent) = self.inner[old].parent() {
+                ids[slot(parent)]
+                    .expect("Parent is renumbered first")
+                    .append(new, &mut inner);
+            }
+            ids[slot(old)] = Some(new);
+        }
+        let new_id = |id: NodeId| ids[slot(id)].expect("Node is live");
+        if self.first.is_indexed() {
+            let mut first = FirstOccurrence::indexed();
+            for role in RoleSet::ALL.role_iter() {
+                first.set(role, self.first.get(role).map(new_id));
+            }
+            self.first = first;
+        }
+        self.root = new_id(self.root);
+        remap_slots(&mut self.counts, &ids, live);
+        remap_slots(&mut self.depths, &ids, live);
+        remap_slots(&mut self.names, &ids, live);
+        self.inner = inner;
+        self.arena_ordered = true;
+        self.skips.invalidate();
+        self.positions.take();
+    }
+    /// Drops every index which is not in `keep` (see [`Tree::drop_index`]), then
+    /// [`Tree::shrink_to_fit`]s the tree.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Tree, TreeConfig, TreeTraversal};
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Section", "children": [{"role": "Link", "children": []}]},
+    /// #     {"role": "Heading", "children": []}
+    /// # ]}"#).unwrap();
+    /// let mut tree = Tree::from_root_node_with(root, TreeConfig::ALL);
+    /// let section = tree.arena()[tree.root()].first_child().unwrap();
+    /// tree.remove_subtree(section);
+    /// let reclaimed = tree.compact(TreeConfig::default());
+    /// assert!(reclaimed.after < reclaimed.before);
+    /// assert!(reclaimed.renumbered);
+    /// assert_eq!(tree.config(), TreeConfig::default());
+    /// assert_eq!(tree.arena().len(), 2);
+    /// assert!(tree.find_first_cached(Role::Heading).is_some());
+    /// ```
+    pub fn compact(&mut self, keep: TreeConfig) -> Reclaimed {
+        let before = self.heap_bytes();
+        // as a set, since not every bit of a roleset has a skip list index of its own
+        let dropped = self.config.skip_lists & !keep.skip_lists;
+        self.config.skip_lists &= keep.skip_lists;
+        self.skips.remove(dropped);
+        for index in self.config.indexes(&UsageProfile::default()) {
+            let kept = match index {
+                Index::Rolesets => keep.rolesets,
+                Index::Counts => keep.counts,
+                Index::Positions => keep.positions,
+                Index::SkipList(_) => true,
+                Index::Ancestors => keep.ancestors,
+            };
+            if !kept {
+                self.drop_index(index);
+            }
+        }
+        let renumbered = self.shrink_to_fit();
+        Reclaimed {
+            before,
+            after: self.heap_bytes(),
+            renumbered,
+        }
+    }
+    /// Compacts the tree (see [`Tree::compact`]) on the [`rayon`] thread pool, then calls
+    /// `on_done` with what was reclaimed; for calling from an idle-time hook, e.g., once the user
+    /// has moved to another document.
+    ///
+    /// The write lock is held while compacting, so queries wait for it.
+    /// If the arena is renumbered, `on_done` is the place to drop any [`NodeId`]s kept from
+    /// before.
+    ///
+    /// # Panics
+    ///
+    /// The spawned task panics if the lock is poisoned.
+    pub fn compact_in_background(
+        tree: &Arc<RwLock<Tree>>,
+        keep: TreeConfig,
+        on_done: impl FnOnce(Reclaimed) + Send + 'static,
+    ) {
+        let tree = Arc::clone(tree);
+        rayon::spawn(move || {
+            let reclaimed = tree.write().expect("Lock is not poisoned").compact(keep);
+            on_done(reclaimed);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{A11yNode, RoleSet, Tree, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+    use std::sync::{mpsc, Arc, RwLock};
+
+    fn node(role: Role, name: Option<&str>, children: Vec<A11yNode>) -> A11yNode {
+        A11yNode {
+            role,
+            name: name.map(String::from),
+            toolkit: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn compaction() {
+        let root = node(
+            Role::Frame,
+            None,
+            vec![
+                node(Role::List, None, vec![node(Role::ListItem, None, vec![])]),
+                node(
+                    Role::Section,
+                    None,
+                    vec![
+                        node(Role::Heading, Some("Kept"), vec![]),
+                        node(Role::Link, None, vec![]),
+                    ],
+                ),
+            ],
+        );
+        let mut tree = Tree::from_root_node_with(root, TreeConfig::ALL);
+        let list = tree.arena()[tree.root()].first_child().expect("Has a list");
+        tree.remove_subtree(list);
+        tree.append_subtree(tree.root(), node(Role::Heading, Some("Added"), vec![]));
+        let expected = Tree::from_root_node_with(
+            node(
+                Role::Frame,
+                None,
+                vec![
+                    node(
+                        Role::Section,
+                        None,
+                        vec![
+                            node(Role::Heading, Some("Kept"), vec![]),
+                            node(Role::Link, None, vec![]),
+                        ],
+                    ),
+                    node(Role::Heading, Some("Added"), vec![]),
+                ],
+            ),
+            TreeConfig::ALL,
+        );
+        let paths = |t: &Tree, role| {
+            let first = t.find_first_cached(role).and_then(|n| t.node_id(n));
+            std::iter::successors(first, |from| {
+                t.find_next_cached(*from, role).and_then(|n| t.node_id(n))
+            })
+            .map(|id| (t.path(id), t.name(id).map(String::from), t.depth(id)))
+            .collect::<Vec<_>>()
+        };
+        // a freed slot was reused by the append, so only one slot is still free
+        assert_eq!(tree.arena().len(), tree.nodes() + 1);
+        let before = tree.heap_bytes();
+        assert!(tree.shrink_to_fit());
+        assert!(tree.heap_bytes() < before);
+        assert_eq!(tree.arena().len(), tree.nodes());
+        // the indexes moved with their nodes, and match a freshly built tree
+        assert_eq!(tree.config(), TreeConfig::ALL);
+        for role in [Role::Frame, Role::Section, Role::Heading, Role::Link] {
+            assert_eq!(paths(&tree, role), paths(&expected, role), "{role:?}");
+            assert_eq!(tree.how_many_roleset(role), expected.how_many_roleset(role));
+        }
+        assert!(!tree.shrink_to_fit());
+        // dropping indexes keeps queries working
+        assert!(!tree.compact(TreeConfig::NONE).renumbered);
+        assert_eq!(tree.config(), TreeConfig::NONE);
+        assert_eq!(tree.skip_list_bytes(), 0);
+        for role in [Role::Frame, Role::Section, Role::Heading, Role::Link] {
+            assert_eq!(paths(&tree, role), paths(&expected, role), "{role:?}");
+        }
+        // in the background
+        let tree = Arc::new(RwLock::new(expected));
+        let (tx, rx) = mpsc::channel();
+        Tree::compact_in_background(&tree, TreeConfig::default(), move |reclaimed| {
+            tx.send(reclaimed).expect("Valid receiver");
+        });
+        let reclaimed = rx.recv().expect("Valid sender");
+        assert!(reclaimed.after < reclaimed.before);
+        assert!(!reclaimed.renumbered);
+        let t = tree.read().expect("Valid lock");
+        assert_eq!(t.config(), TreeConfig::default());
+        assert_eq!(t.skip_list_roles(), RoleSet::EMPTY);
+    }
+}
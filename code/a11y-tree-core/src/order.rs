@@ -0,0 +1,168 @@
+//! Frequency-based bit orders for role sets.
+//!
+//! [`RoleSet`] gives each role a fixed bit, by its numeric value.
+//! A [`RoleOrder`] instead gives the roles which are most common in a tree the lowest bits, so
+//! that checks for common roles only need the first machine word of an [`OrderedRoleSet`].
+//!
+//! The `role_set/bit_order` benchmark measures this in a pruning loop; on the real data it makes
+//! no material difference, since 128-bit masks are compared without branches either way.
+//! So trees keep using [`RoleSet`]; a tree's order is kept in its serialized header (see
+//! [`Tree::role_order`](crate::Tree::role_order)) for consumers which store their own sets.
+
+use crate::role_set::ROLE_BITS;
+use crate::RoleSet;
+use atspi_common::Role;
+use serde::{Deserialize, Serialize};
+
+/// An assignment of roles to bits, from the lowest bit up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Role>", into = "Vec<Role>")]
+pub struct RoleOrder {
+    /// The role for each bit.
+    roles: Vec<Role>,
+    /// The bit for each role, by numeric value.
+    bits: Vec<u8>,
+}
+
+impl From<Vec<Role>> for RoleOrder {
+    /// Roles which are missing from `roles` are given the following bits, in numeric order;
+    /// repeated roles keep their first bit.
+    /// [`Role::Invalid`] is never in a set, so it has no bit.
+    fn from(roles: Vec<Role>) -> Self {
+        let mut order = RoleOrder {
+            roles: Vec::with_capacity(ROLE_BITS),
+            bits: vec![u8::MAX; ROLE_BITS],
+        };
+        let all = RoleSet::ALL.role_iter();
+        for role in roles.into_iter().chain(all) {
+            if role != Role::Invalid && order.bits[role as usize] == u8::MAX {
+                order.bits[role as usize] =
+                    u8::try_from(order.roles.len()).expect("Fewer than 256 roles");
+                order.roles.push(role);
+            }
+        }
+        order
+    }
+}
+
+impl From<RoleOrder> for Vec<Role> {
+    fn from(order: RoleOrder) -> Self {
+        order.roles
+    }
+}
+
+impl Default for RoleOrder {
+    /// Roles in numeric order.
+    fn default() -> Self {
+        RoleOrder::from(Vec::new())
+    }
+}
+
+impl RoleOrder {
+    /// An order with the most common roles in `counts` first; ties keep their numeric order.
+    #[must_use]
+    pub fn by_frequency(counts: &[(Role, usize)]) -> Self {
+        let mut counts = counts.to_vec();
+        counts.sort_by_key(|(role, count)| (std::cmp::Reverse(*count), *role as u32));
+        RoleOrder::from(counts.into_iter().map(|(role, _)| role).collect::<Vec<_>>())
+    }
+    /// The role for each bit, from the lowest bit up.
+    #[must_use]
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+    /// The set of `roles`, with this order's bits.
+    #[must_use]
+    pub fn set(&self, roles: RoleSet) -> OrderedRoleSet {
+        let roles = roles.role_iter().filter(|role| *role != Role::Invalid);
+        roles.fold(OrderedRoleSet(0, 0), |set, role| {
+            let bit = self.bits[role as usize];
+            match bit.checked_sub(128) {
+                None => OrderedRoleSet(set.0 | 1 << bit, set.1),
+                Some(bit) => OrderedRoleSet(set.0, set.1 | 1 << bit),
+            }
+        })
+    }
+}
+
+/// A set of roles with the bits of a [`RoleOrder`]; only comparable with sets from the same order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OrderedRoleSet(u128, u8);
+
+impl OrderedRoleSet {
+    /// Check if the set contains all roles in `other`; if `other` only has roles in the first
+    /// machine word, only that word is compared.
+    ///
+    /// ```
+    /// use a11y_tree_core::order::RoleOrder;
+    /// use a11y_tree_core::RoleSet;
+    /// use atspi_common::Role;
+    /// let order = RoleOrder::by_frequency(&[(Role::Link, 2), (Role::Heading, 3)]);
+    /// assert_eq!(&order.roles()[..2], [Role::Heading, Role::Link]);
+    /// let set = order.set(RoleSet::EMPTY | Role::Heading | Role::Mark);
+    /// assert!(set.contains(order.set(Role::Heading.into())));
+    /// assert!(!set.contains(order.set(Role::Link.into())));
+    /// ```
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn contains(self, other: OrderedRoleSet) -> bool {
+        if other.0 >> 64 == 0 && other.1 == 0 {
+            let low = other.0 as u64;
+            return self.0 as u64 & low == low;
+        }
+        self.0 & other.0 == other.0 && self.1 & other.1 == other.1
+    }
+    /// Check if the set contains _any_ of the roles in `other`.
+    #[must_use]
+    pub fn intersects(self, other: OrderedRoleSet) -> bool {
+        self.0 & other.0 != 0 || self.1 & other.1 != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoleOrder;
+    use crate::RoleSet;
+    use atspi_common::Role;
+
+    #[test]
+    fn role_order() {
+        let identity = RoleOrder::default();
+        assert_eq!(identity.roles().len(), RoleSet::ALL.len() - 1);
+        let order = RoleOrder::by_frequency(&[
+            (Role::Suggestion, 10),
+            (Role::Link, 20),
+            (Role::Heading, 10),
+        ]);
+        assert_eq!(
+            &order.roles()[..4],
+            [
+                Role::Link,
+                Role::Heading,
+                Role::Suggestion,
+                Role::AcceleratorLabel
+            ]
+        );
+        let json = serde_json::to_string(&order).expect("Valid order");
+        assert_eq!(
+            serde_json::from_str::<RoleOrder>(&json).expect("Valid JSON"),
+            order
+        );
+        // every order agrees with RoleSet on which sets contain which
+        let sets = [
+            RoleSet::EMPTY,
+            RoleSet::ALL,
+            RoleSet::EMPTY | Role::Suggestion | Role::PushButtonMenu,
+            RoleSet::EMPTY | Role::Link | Role::Mark,
+            Role::Heading.into(),
+        ];
+        for order in [&identity, &order] {
+            for a in sets {
+                for b in sets {
+                    assert_eq!(order.set(a).contains(order.set(b)), a.contains(b));
+                    assert_eq!(order.set(a).intersects(order.set(b)), a.intersects(b));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::Index;
+use std::fmt::{self, Display, Formatter};
+
+/// How a query is answered, given the indexes a tree has built.
+///
+/// Methods which use an index fall back to visiting nodes when it is missing, so the plan is how
+/// to tell whether a query is as fast as intended.
+/// Each such method returns the plan it took from its `*_planned` variant (e.g.,
+/// [`crate::TreeTraversal::find_next_cached_planned`]), and [`crate::Step::run_planned`] reports
+/// it for any recorded query.
+///
+/// ```
+/// use a11y_tree_core::{Index, QueryPlan, Tree, TreeConfig, TreeTraversal};
+/// use atspi_common::Role;
+/// # let root: a11y_tree_core::A11yNode = serde_json::from_str(r#"{"role": "Frame", "children": [
+/// #     {"role": "Link", "children": []}
+/// # ]}"#).unwrap();
+/// let tree = Tree::from_root_node_with(root.clone(), TreeConfig::ALL);
+/// assert_eq!(tree.how_many_roleset_planned(Role::Link), (1, QueryPlan::Lookup(Index::Counts)));
+/// let tree = Tree::from_root_node_with(root, TreeConfig::NONE);
+/// assert_eq!(tree.how_many_roleset_planned(Role::Link), (1, QueryPlan::Fallback(Index::Rolesets)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryPlan {
+    /// Looked up in an index, without visiting nodes.
+    Lookup(Index),
+    /// Visits nodes, skipping subtrees whose roleset rules them out.
+    Pruned,
+    /// Visits every node.
+    Scan,
+    /// The method uses an index which has not been built (or, for skip lists, is still being
+    /// warmed up), so it visits nodes instead.
+    Fallback(Index),
+}
+
+impl Display for QueryPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryPlan::Lookup(index) => write!(f, "lookup in {index}"),
+            QueryPlan::Pruned => write!(f, "pruned traversal"),
+            QueryPlan::Scan => write!(f, "full scan"),
+            QueryPlan::Fallback(index) => write!(f, "full scan ({index} not built)"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Policies which decide how roles are treated when reading or navigating a tree.
+
+use crate::RoleSet;
+use atspi_common::Role;
+
+/// Roles which a user can interact with directly.
+pub const INTERACTIVE: RoleSet = RoleSet::from_roles(&[
+    Role::Button,
+    Role::CheckBox,
+    Role::CheckMenuItem,
+    Role::ComboBox,
+    Role::Entry,
+    Role::Link,
+    Role::ListBox,
+    Role::MenuItem,
+    Role::PageTab,
+    Role::PasswordText,
+    Role::PushButtonMenu,
+    Role::RadioButton,
+    Role::RadioMenuItem,
+    Role::ScrollBar,
+    Role::Slider,
+    Role::SpinButton,
+    Role::Text,
+    Role::ToggleButton,
+]);
+
+/// Roles which usually carry text or other content that is read out to the user.
+pub const TEXTUAL: RoleSet = RoleSet::from_roles(&[
+    Role::Caption,
+    Role::Heading,
+    Role::Image,
+    Role::Label,
+    Role::Paragraph,
+    Role::Static,
+    Role::TableCell,
+]);
+
+/// Decides when a subtree is "effectively empty": when it contains nothing worth announcing.
+///
+/// Since nodes only store their role, content is approximated by role: a subtree is empty if
+/// none of its nodes have one of the [`EmptinessPolicy::content`] roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmptinessPolicy {
+    /// Roles which count as content.
+    pub content: RoleSet,
+}
+
+impl EmptinessPolicy {
+    /// Only interactive roles count as content.
+    pub const INTERACTIVE_ONLY: EmptinessPolicy = EmptinessPolicy {
+        content: INTERACTIVE,
+    };
+}
+
+impl Default for EmptinessPolicy {
+    /// Both textual and interactive roles count as content.
+    fn default() -> Self {
+        EmptinessPolicy {
+            content: TEXTUAL | INTERACTIVE,
+        }
+    }
+}
+
+/// Roles which are read as a block of their own, separated from the surrounding text.
+pub const BLOCKS: RoleSet = RoleSet::from_roles(&[
+    Role::Article,
+    Role::BlockQuote,
+    Role::Caption,
+    Role::DescriptionTerm,
+    Role::DescriptionValue,
+    Role::Footer,
+    Role::Footnote,
+    Role::Form,
+    Role::Header,
+    Role::Heading,
+    Role::Landmark,
+    Role::List,
+    Role::ListItem,
+    Role::Paragraph,
+    Role::Section,
+    Role::Table,
+    Role::TableRow,
+]);
+
+/// Roles which are cells in a row, separated from their siblings.
+pub const CELLS: RoleSet =
+    RoleSet::from_roles(&[Role::ColumnHeader, Role::RowHeader, Role::TableCell]);
+
+/// How strongly two pieces of text are separated; the strongest break between them wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Break {
+    Word,
+    Cell,
+    Block,
+}
+
+/// Decides how text is joined when a container is read as a whole; see
+/// [`crate::Tree::flatten_text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextPolicy {
+    /// Roles whose text is separated from the surrounding text by `block_separator`.
+    pub blocks: RoleSet,
+    /// Roles whose text is separated from their siblings' by `cell_separator`.
+    pub cells: RoleSet,
+    /// Separates blocks, e.g., paragraphs.
+    pub block_separator: &'static str,
+    /// Separates cells, e.g., in a table row.
+    pub cell_separator: &'static str,
+    /// Separates any other text.
+    pub word_separator: &'static str,
+}
+
+impl TextPolicy {
+    /// The break caused by a node with `role` starting or ending.
+    pub(crate) fn break_at(&self, role: Role) -> Break {
+        let role = RoleSet::from(role);
+        if self.blocks.intersects(role) {
+            Break::Block
+        } else if self.cells.intersects(role) {
+            Break::Cell
+        } else {
+            Break::Word
+        }
+    }
+    /// The separator for a break.
+    pub(crate) fn separator(&self, at: Break) -> &'static str {
+        match at {
+            Break::Word => self.word_separator,
+            Break::Cell => self.cell_separator,
+            Break::Block => self.block_separator,
+        }
+    }
+}
+
+impl Default for TextPolicy {
+    /// Blocks are separated by a line break, and cells by a tab.
+    fn default() -> Self {
+        TextPolicy {
+            blocks: BLOCKS,
+            cells: CELLS,
+            block_separator: "\n",
+            cell_separator: "\t",
+            word_separator: " ",
+        }
+    }
+}
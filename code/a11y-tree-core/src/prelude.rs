@@ -0,0 +1,22 @@
+//! The commonly used items, so that downstream code does not depend on this crate's module
+//! layout.
+//!
+//! ```
+//! use a11y_tree_core::prelude::*;
+//! let root: A11yNode = serde_json::from_str(
+//!     r#"{"role": "Frame", "children": [{"role": "Heading", "children": []}]}"#,
+//! )
+//! .unwrap();
+//! let tree = Tree::from_root_node_with(root, TreeConfig::default());
+//! assert!(tree.find_first_roleset(Role::Heading).is_some());
+//! assert!(tree.unique_roles_roleset().contains(RoleSet::from(Role::Frame)));
+//! ```
+
+pub use crate::flags::Flags;
+pub use crate::lazy::LazyTree;
+pub use crate::policy::{EmptinessPolicy, TextPolicy};
+pub use crate::{
+    A11yNode, HasRole, NodeIdExt, QueryPlan, RoleSet, Tree, TreeConfig, TreeCount, TreeTraversal,
+};
+pub use atspi_common::Role;
+pub use indextree::NodeId;
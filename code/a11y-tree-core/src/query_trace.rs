@@ -0,0 +1,76 @@
+//! The nodes a query visited, for checking which subtrees an index let it skip.
+//!
+//! Only sequential queries are traced: the nodes a parallel query visits, and their order, depend
+//! on how its work was scheduled.
+
+use crate::TreeTraversal;
+use indextree::NodeId;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+/// The result of a query, and every node it visited, in the order it visited them (see
+/// [`TreeTraversal::find_first_traced`]).
+///
+/// ```
+/// use a11y_tree_core::{Tree, TreeConfig, TreeTraversal};
+/// use atspi_common::Role;
+/// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+/// #     {"role": "Section", "children": [
+/// #         {"role": "Link", "children": []},
+/// #         {"role": "Link", "children": []}
+/// #     ]},
+/// #     {"role": "Heading", "children": []}
+/// # ]}"#).unwrap();
+/// let tree = Tree::from_root_node_with(root, TreeConfig::default());
+/// // a full scan visits the section and both of its links before the heading...
+/// let scan = tree.find_first_traced(Role::Heading);
+/// assert_eq!(scan.runs(&tree), [0..=4]);
+/// // ...but the section's roleset has no heading, so a pruned traversal skips it
+/// let pruned = tree.find_first_roleset_traced(Role::Heading);
+/// assert_eq!(pruned.found, scan.found);
+/// assert_eq!(pruned.runs(&tree), [0..=0, 4..=4]);
+/// let section = tree.arena()[tree.root()].first_child().unwrap();
+/// assert!(pruned.skipped(&tree, section));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryTrace {
+    /// What the query found.
+    pub found: Option<NodeId>,
+    /// The nodes the query visited, in order.
+    pub visited: Vec<NodeId>,
+}
+
+impl QueryTrace {
+    /// Returns whether the query visited no node in the subtree rooted at `node` (including
+    /// `node` itself).
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in `tree`.
+    #[must_use]
+    pub fn skipped<T: TreeTraversal + ?Sized>(&self, tree: &T, node: NodeId) -> bool {
+        let visited = self.visited.iter().collect::<HashSet<_>>();
+        !node
+            .descendants(tree.arena())
+            .any(|id| visited.contains(&id))
+    }
+    /// Summarizes the visited nodes as runs of consecutive document order positions (the root is
+    /// `0`), which is much shorter than the nodes themselves when large subtrees are skipped.
+    #[must_use]
+    pub fn runs<T: TreeTraversal + ?Sized>(&self, tree: &T) -> Vec<RangeInclusive<usize>> {
+        let positions = tree
+            .root()
+            .descendants(tree.arena())
+            .enumerate()
+            .map(|(i, id)| (id, i))
+            .collect::<HashMap<_, _>>();
+        let mut runs: Vec<RangeInclusive<usize>> = Vec::new();
+        for position in self.visited.iter().filter_map(|id| positions.get(id)) {
+            match runs.last_mut() {
+                Some(run) if run.end() + 1 == *position => *run = *run.start()..=*position,
+                _ => runs.push(*position..=*position),
+            }
+        }
+        runs
+    }
+}
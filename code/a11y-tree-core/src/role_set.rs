@@ -8,6 +8,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Default, Copy, Clone, PartialEq, Serialize, Deserialize, Eq)]
 pub struct RoleSet(u128, u8);
 
+/// The number of bits in a [`RoleSet`], so a table indexed by `role as usize` has a slot for
+/// every [`Role`].
+pub(crate) const ROLE_BITS: usize = (u128::BITS + u8::BITS) as usize;
+
 impl RoleSet {
     /// A [`RoleSet`] representing no roles in a bitset.
     pub const EMPTY: RoleSet = RoleSet(0, 0);
@@ -18,7 +22,7 @@ impl RoleSet {
     /// This does not allocate and is cheap.
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSet;
+    /// use a11y_tree_core::RoleSet;
     /// let rs = RoleSet::from_role(Role::DesktopFrame);
     /// let mut iter = rs.role_iter();
     /// assert_eq!(iter.next(), Some(Role::DesktopFrame));
@@ -28,11 +32,32 @@ impl RoleSet {
         let (low, high) = role_bits(role);
         RoleSet(low, high)
     }
+    /// Create a [`RoleSet`] from a list of roles.
+    /// This is a `const fn`, so it can be used to define role groups at compile time.
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSet;
+    /// const LANDMARKS: RoleSet = RoleSet::from_roles(&[Role::Landmark, Role::Form]);
+    /// assert!(LANDMARKS.contains(Role::Form.into()));
+    /// assert!(!LANDMARKS.contains(Role::Button.into()));
+    /// ```
+    #[must_use]
+    pub const fn from_roles(roles: &[Role]) -> Self {
+        let mut rs = RoleSet::EMPTY;
+        let mut i = 0;
+        while i < roles.len() {
+            let (low, high) = role_bits(roles[i]);
+            rs.0 |= low;
+            rs.1 |= high;
+            i += 1;
+        }
+        rs
+    }
     /// Check if [`RoleSet`] contains all roles in `other`.
     ///
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSet;
+    /// use a11y_tree_core::RoleSet;
     /// let rs = RoleSet::ALL;
     /// assert!(rs.contains(Role::Frame.into()));
     /// assert!(rs.contains(Role::Button.into()));
@@ -44,11 +69,39 @@ impl RoleSet {
     pub fn contains(self, other: RoleSet) -> bool {
         (self & other) == other
     }
+    /// Check if [`RoleSet`] contains _any_ of the roles in `other`.
+    ///
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSet;
+    /// let rs = RoleSet::EMPTY | Role::Heading | Role::Link;
+    /// assert!(rs.intersects(RoleSet::EMPTY | Role::Link | Role::Button));
+    /// assert!(!rs.intersects(Role::Button.into()));
+    /// ```
+    #[must_use]
+    pub fn intersects(self, other: RoleSet) -> bool {
+        !(self & other).is_empty()
+    }
+    /// Check if [`RoleSet`] contains no roles at all.
+    ///
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSet;
+    /// assert!(RoleSet::EMPTY.is_empty());
+    /// assert!(!RoleSet::from_role(Role::Link).is_empty());
+    /// ```
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self == RoleSet::EMPTY
+    }
     /// Iterate over the individual roles contained within the bitset.
     /// This translates each flipped bit into the corresponding [`Role`].
+    ///
+    /// Roles are always yielded in ascending numeric order (i.e., `role as u32`), regardless of
+    /// the order in which they were added to the set.
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSet;
+    /// use a11y_tree_core::RoleSet;
     /// let mut roles = RoleSet::ALL.role_iter();
     /// assert!(roles.next().is_some());
     /// assert!(roles.next().is_some());
@@ -59,23 +112,92 @@ impl RoleSet {
     /// assert!(roles.next().is_none());
     /// ```
     pub fn role_iter(self) -> impl Iterator<Item = Role> {
-        (0..u128::BITS)
-            .filter(move |i| (self.0 >> i) & 0x1 == 1)
-            .filter_map(|i| Role::try_from(i).ok())
-            .chain(
-                (0..u8::BITS)
-                    .filter(move |i| (self.1 >> i) & 0x1 == 1)
-                    .map_while(|i| Role::try_from(i + u128::BITS).ok()),
-            )
+        // jump from one set bit to the next, rather than testing every bit
+        let (mut low, mut high) = (self.0, self.1 & HIGH_ROLES);
+        core::iter::from_fn(move || {
+            let i = if low != 0 {
+                let i = low.trailing_zeros();
+                low &= low - 1;
+                i
+            } else if high != 0 {
+                let i = high.trailing_zeros();
+                high &= high - 1;
+                i + u128::BITS
+            } else {
+                return None;
+            };
+            Role::try_from(i).ok()
+        })
     }
 }
 
+/// The bits of [`RoleSet`]'s high byte which correspond to a [`Role`] (the rest are padding).
+const HIGH_ROLES: u8 = (1 << (ROLES - u128::BITS)) - 1;
+/// The number of [`Role`]s; their numeric values are `0..ROLES`.
+const ROLES: u32 = Role::PushButtonMenu as u32 + 1;
+
+impl RoleSet {
+    /// The maximum number of roles shown by [`RoleSet`]'s [`fmt::Display`] implementation, unless
+    /// the alternate (`{:#}`) form is used.
+    pub const DISPLAY_LIMIT: usize = 8;
+
+    /// The number of roles contained within the bitset.
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSet;
+    /// assert_eq!(RoleSet::EMPTY.len(), 0);
+    /// assert_eq!((RoleSet::EMPTY | Role::Link | Role::Heading).len(), 2);
+    /// assert_eq!(RoleSet::ALL.len(), 130);
+    /// ```
+    #[must_use]
+    pub fn len(self) -> usize {
+        (self.0.count_ones() + (self.1 & HIGH_ROLES).count_ones()) as usize
+    }
+}
+
+/// Lists the full set of roles, by variant name.
 impl fmt::Debug for RoleSet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_set().entries(self.role_iter()).finish()
     }
 }
 
+/// Lists the roles by their human-readable name.
+/// Only the first [`RoleSet::DISPLAY_LIMIT`] roles are shown, followed by a count of the rest;
+/// use the alternate form (`{:#}`) to list all of them.
+///
+/// ```
+/// use atspi_common::Role;
+/// use a11y_tree_core::RoleSet;
+/// let rs = RoleSet::EMPTY | Role::Heading | Role::Link;
+/// assert_eq!(format!("{rs}"), "{heading, link}");
+/// assert_eq!(format!("{}", RoleSet::EMPTY), "{}");
+/// let all = format!("{}", RoleSet::ALL);
+/// assert!(all.ends_with(", ... (122 more)}"));
+/// assert_eq!(format!("{:#}", RoleSet::ALL).matches(", ").count(), 129);
+/// ```
+impl fmt::Display for RoleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = if f.alternate() {
+            usize::MAX
+        } else {
+            RoleSet::DISPLAY_LIMIT
+        };
+        write!(f, "{{")?;
+        for (i, role) in self.role_iter().take(limit).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{role}")?;
+        }
+        let rest = self.len().saturating_sub(limit);
+        if rest > 0 {
+            write!(f, ", ... ({rest} more)")?;
+        }
+        write!(f, "}}")
+    }
+}
+
 impl From<Role> for RoleSet {
     fn from(r: Role) -> Self {
         let (low, high) = role_bits(r);
@@ -318,6 +440,24 @@ mod tests {
         assert_eq!(!no_roles, all_roles);
     }
 
+    #[test]
+    fn check_display_truncation() {
+        let mut rs = RoleSet::EMPTY;
+        // `Role::Invalid` has no bit of its own, so skip it
+        for role in RoleSet::ALL
+            .role_iter()
+            .skip(1)
+            .take(RoleSet::DISPLAY_LIMIT)
+        {
+            rs |= role;
+        }
+        // exactly at the limit, nothing is truncated
+        assert_eq!(format!("{rs}"), format!("{rs:#}"));
+        rs |= Role::PushButtonMenu;
+        assert!(format!("{rs}").ends_with(", ... (1 more)}"), "{rs}");
+        assert!(format!("{rs:#}").ends_with(", push button menu}"), "{rs:#}");
+    }
+
     #[test]
     fn check_bits_and_assign() {
         let less_roles = RoleSet::EMPTY | Role::Frame | Role::Link;
@@ -343,7 +483,7 @@ impl RoleSetVecCount {
     /// This _does_ allocate, and should be used with care in a hot loop.
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSetVecCount;
+    /// use a11y_tree_core::RoleSetVecCount;
     /// let _rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
     /// ```
     #[must_use]
@@ -360,10 +500,27 @@ impl RoleSetVecCount {
             .unwrap_or_default()
     }
 
+    /// Iterate over each role and the number of nodes with that role.
+    /// Roles are yielded in the order they were first added, not in numeric order.
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSetVecCount;
+    /// let mut rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
+    /// rsvc.add(Role::Button);
+    /// rsvc.add(Role::Button);
+    /// let mut iter = rsvc.iter();
+    /// assert_eq!(iter.next(), Some((Role::DesktopFrame, 1)));
+    /// assert_eq!(iter.next(), Some((Role::Button, 2)));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Role, usize)> + '_ {
+        self.0.iter().copied()
+    }
+
     /// Checks against a `RoleSet`.
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSetVecCount;
+    /// use a11y_tree_core::RoleSetVecCount;
     /// let rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
     /// assert!(rsvc.contains(Role::DesktopFrame.into()));
     /// assert!(!rsvc.contains(Role::Button.into()));
@@ -376,7 +533,7 @@ impl RoleSetVecCount {
     /// or placing `0` in that slot, if it doesn't.
     /// ```
     /// use atspi_common::Role;
-    /// use indextree_method_structural_nav::RoleSetVecCount;
+    /// use a11y_tree_core::RoleSetVecCount;
     /// let mut rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
     /// assert!(rsvc.contains(Role::DesktopFrame.into()));
     /// assert!(!rsvc.contains(Role::Button.into()));
@@ -391,6 +548,58 @@ impl RoleSetVecCount {
         };
         pair.1 += 1;
     }
+    /// Add all counts from `other`.
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSetVecCount;
+    /// let mut rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
+    /// let mut other = RoleSetVecCount::from_role(Role::Button);
+    /// other.add(Role::DesktopFrame);
+    /// rsvc.merge(&other);
+    /// assert_eq!(rsvc.count(Role::DesktopFrame), 2);
+    /// assert_eq!(rsvc.count(Role::Button), 1);
+    /// ```
+    pub fn merge(&mut self, other: &RoleSetVecCount) {
+        self.1 |= other.1;
+        for (role, count) in other.iter() {
+            match self.0.iter_mut().find(|pair| pair.0 == role) {
+                Some(pair) => pair.1 += count,
+                None => self.0.push((role, count)),
+            }
+        }
+    }
+    /// Remove all counts in `other`; roles whose count drops to `0` are removed from the set.
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::RoleSetVecCount;
+    /// let mut rsvc = RoleSetVecCount::from_role(Role::DesktopFrame);
+    /// rsvc.add(Role::Button);
+    /// rsvc.add(Role::Button);
+    /// rsvc.subtract(&RoleSetVecCount::from_role(Role::Button));
+    /// assert_eq!(rsvc.count(Role::Button), 1);
+    /// rsvc.subtract(&RoleSetVecCount::from_role(Role::DesktopFrame));
+    /// assert!(!rsvc.contains(Role::DesktopFrame.into()));
+    /// ```
+    pub fn subtract(&mut self, other: &RoleSetVecCount) {
+        for (role, count) in other.iter() {
+            if let Some(pair) = self.0.iter_mut().find(|pair| pair.0 == role) {
+                pair.1 = pair.1.saturating_sub(count);
+            }
+        }
+        self.0.retain(|pair| pair.1 > 0);
+        self.1 = self
+            .0
+            .iter()
+            .fold(RoleSet::EMPTY, |roles, pair| roles | pair.0);
+    }
+    /// Returns the memory used by the counts, in bytes (excluding the struct itself).
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.0.capacity() * size_of::<(Role, usize)>()
+    }
+    /// Frees any spare capacity left by [`RoleSetVecCount::subtract`].
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit();
+    }
 }
 
 impl From<Role> for RoleSetVecCount {
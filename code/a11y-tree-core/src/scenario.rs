@@ -0,0 +1,198 @@
+use crate::{QueryPlan, RoleSet, TreeTraversal};
+use atspi_common::Role;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A recorded sequence of queries against a single data file.
+///
+/// Scenarios are written by the REPL's `--record` option, and replayed by the benchmarks, so that
+/// a slow navigation pattern found interactively can be turned into a benchmark.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scenario {
+    /// Path to the data file the scenario was recorded against.
+    pub data: String,
+    /// The queries, in the order they were run.
+    pub steps: Vec<Step>,
+}
+
+/// A single query against a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Step {
+    /// [`TreeTraversal::find_first`]
+    FindFirst {
+        /// The role to search for.
+        role: Role,
+    },
+    /// [`TreeTraversal::find_first_roleset`]
+    FindFirstRoleset {
+        /// The role to search for.
+        role: Role,
+    },
+    /// [`TreeTraversal::find_first_stack`]
+    FindFirstStack {
+        /// The role to search for.
+        role: Role,
+    },
+    /// [`TreeTraversal::how_many`]
+    HowMany {
+        /// The role to count.
+        role: Role,
+    },
+    /// [`TreeTraversal::how_many_roleset`]
+    HowManyRoleset {
+        /// The role to count.
+        role: Role,
+    },
+    /// [`TreeTraversal::unique_roles`]
+    UniqueRoles,
+    /// [`TreeTraversal::max_depth`]
+    MaxDepth,
+}
+
+/// The result of running a [`Step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutput {
+    /// The path to the node found (see [`TreeTraversal::path`]), if any.
+    Node(Option<Vec<usize>>),
+    /// A number of nodes, or a depth.
+    Count(usize),
+    /// A set of roles.
+    Roles(RoleSet),
+}
+
+impl Step {
+    /// Returns the role the step queries, if any.
+    #[must_use]
+    pub fn role(self) -> Option<Role> {
+        match self {
+            Step::FindFirst { role }
+            | Step::FindFirstRoleset { role }
+            | Step::FindFirstStack { role }
+            | Step::HowMany { role }
+            | Step::HowManyRoleset { role } => Some(role),
+            Step::UniqueRoles | Step::MaxDepth => None,
+        }
+    }
+    /// Run the query against a tree.
+    pub fn run<T: TreeTraversal>(self, tree: &T) -> StepOutput {
+        self.run_planned(tree).0
+    }
+    /// Run the query against a tree, also returning the path it took.
+    ///
+    /// ```
+    /// use a11y_tree_core::{Index, QueryPlan, Step, Tree, TreeConfig};
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": []}"#).unwrap();
+    /// let tree = Tree::from_root_node_with(root, TreeConfig::default());
+    /// let (_, plan) = Step::HowManyRoleset { role: Role::Link }.run_planned(&tree);
+    /// assert_eq!(plan, QueryPlan::Pruned);
+    /// assert_eq!(Step::MaxDepth.run_planned(&tree).1, QueryPlan::Scan);
+    /// ```
+    pub fn run_planned<T: TreeTraversal>(self, tree: &T) -> (StepOutput, QueryPlan) {
+        let path = |(node, plan): (Option<&indextree::Node<T::Node>>, QueryPlan)| {
+            let path = node
+                .and_then(|node| tree.node_id(node))
+                .map(|id| tree.path(id));
+            (StepOutput::Node(path), plan)
+        };
+        let count = |(count, plan)| (StepOutput::Count(count), plan);
+        match self {
+            Step::FindFirst { role } => path((tree.find_first(role), QueryPlan::Scan)),
+            Step::FindFirstRoleset { role } => path(tree.find_first_roleset_planned(role)),
+            Step::FindFirstStack { role } => path(tree.find_first_stack_planned(role)),
+            Step::HowMany { role } => count((tree.how_many(role), QueryPlan::Scan)),
+            Step::HowManyRoleset { role } => count(tree.how_many_roleset_planned(role)),
+            Step::UniqueRoles => (StepOutput::Roles(tree.unique_roles()), QueryPlan::Scan),
+            Step::MaxDepth => count((tree.max_depth(), QueryPlan::Scan)),
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+    /// Parse a step from a line of the form `method [role]`, e.g., `find_first heading`.
+    ///
+    /// ```
+    /// use atspi_common::Role;
+    /// use a11y_tree_core::Step;
+    /// assert_eq!("how_many link".parse(), Ok(Step::HowMany { role: Role::Link }));
+    /// assert_eq!("max_depth".parse(), Ok(Step::MaxDepth));
+    /// assert!("find_first".parse::<Step>().is_err());
+    /// ```
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (method, role) = match line.split_once(char::is_whitespace) {
+            Some((method, role)) => (method, Some(role.trim())),
+            None => (line, None),
+        };
+        let role = || {
+            let name = role.ok_or_else(|| format!("`{method}` requires a role"))?;
+            parse_role(name).ok_or_else(|| format!("Unknown role: {name}"))
+        };
+        Ok(match method {
+            "find_first" => Step::FindFirst { role: role()? },
+            "find_first_roleset" => Step::FindFirstRoleset { role: role()? },
+            "find_first_stack" => Step::FindFirstStack { role: role()? },
+            "how_many" => Step::HowMany { role: role()? },
+            "how_many_roleset" => Step::HowManyRoleset { role: role()? },
+            "unique_roles" => Step::UniqueRoles,
+            "max_depth" => Step::MaxDepth,
+            _ => return Err(format!("Unknown method: {method}")),
+        })
+    }
+}
+
+impl Display for StepOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutput::Node(Some(path)) => write!(f, "found at path {path:?}"),
+            StepOutput::Node(None) => write!(f, "not found"),
+            StepOutput::Count(count) => write!(f, "{count}"),
+            StepOutput::Roles(roles) => write!(f, "{roles:#}"),
+        }
+    }
+}
+
+/// Parse a role from its variant name (`PushButtonMenu`) or AT-SPI name (`push button menu`).
+/// Case is ignored.
+///
+/// ```
+/// use atspi_common::Role;
+/// use a11y_tree_core::parse_role;
+/// assert_eq!(parse_role("PushButtonMenu"), Some(Role::PushButtonMenu));
+/// assert_eq!(parse_role("push button menu"), Some(Role::PushButtonMenu));
+/// assert_eq!(parse_role("not a role"), None);
+/// ```
+#[must_use]
+pub fn parse_role(name: &str) -> Option<Role> {
+    RoleSet::ALL.role_iter().find(|role| {
+        format!("{role:?}").eq_ignore_ascii_case(name) || role.name().eq_ignore_ascii_case(name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scenario, Step};
+    use atspi_common::Role;
+
+    #[test]
+    fn scenario_json_round_trip() {
+        let scenario = Scenario {
+            data: "../../data/single-page-html-spec.json".to_string(),
+            steps: vec![
+                Step::FindFirst {
+                    role: Role::Heading,
+                },
+                Step::HowManyRoleset { role: Role::Link },
+                Step::MaxDepth,
+            ],
+        };
+        let json = serde_json::to_string(&scenario).expect("Valid JSON");
+        assert_eq!(
+            serde_json::from_str::<Scenario>(&json).expect("Valid scenario"),
+            scenario
+        );
+    }
+}
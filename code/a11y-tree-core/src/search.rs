@@ -0,0 +1,353 @@
+//! Finding nodes by their accessible name.
+//!
+//! How names match a query is up to a [`NameMatcher`].
+//! [`CaseInsensitive`] folds case only; with the `icu_collator` feature, [`Collated`] also
+//! ignores diacritics, using the rules of a given locale, so that typing "uber" finds "Über".
+//!
+//! For "jump to element by typing", [`Tree::search_ranked`] orders matches by a [`Scorer`]; the
+//! default, [`Weighted`], prefers better matches, headings and landmarks, and earlier nodes.
+
+use crate::{HasRole, RoleSet, Tree, TreeTraversal};
+use atspi_common::Role;
+use indextree::NodeId;
+
+/// How well a name matches a query, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchQuality {
+    /// The query is somewhere in the name.
+    Contains,
+    /// The query is at the start of a word in the name.
+    WordStart,
+    /// The name starts with the query.
+    Prefix,
+    /// The name is the query.
+    Exact,
+}
+
+impl MatchQuality {
+    /// The quality of a match of `name[start..end]`.
+    fn at(name: &str, start: usize, end: usize) -> Self {
+        if start == 0 && end == name.len() {
+            MatchQuality::Exact
+        } else if start == 0 {
+            MatchQuality::Prefix
+        } else if name[..start].ends_with(|c: char| !c.is_alphanumeric()) {
+            MatchQuality::WordStart
+        } else {
+            MatchQuality::Contains
+        }
+    }
+}
+
+/// Decides whether, and how well, a name matches a query.
+pub trait NameMatcher {
+    /// The query, prepared once per search.
+    type Query;
+    /// Prepare `query` for matching against many names.
+    fn prepare(&self, query: &str) -> Self::Query;
+    /// How well `name` matches `query`, or `None` if it does not contain it.
+    fn quality(&self, name: &str, query: &Self::Query) -> Option<MatchQuality>;
+    /// Whether `name` contains `query`.
+    fn matches(&self, name: &str, query: &Self::Query) -> bool {
+        self.quality(name, query).is_some()
+    }
+}
+
+/// Matches names which contain the query, ignoring case.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CaseInsensitive;
+
+impl NameMatcher for CaseInsensitive {
+    type Query = String;
+    fn prepare(&self, query: &str) -> String {
+        query.to_lowercase()
+    }
+    fn quality(&self, name: &str, query: &String) -> Option<MatchQuality> {
+        let name = name.to_lowercase();
+        if query.is_empty() {
+            return Some(MatchQuality::at(&name, 0, 0));
+        }
+        name.match_indices(query.as_str())
+            .map(|(start, _)| MatchQuality::at(&name, start, start + query.len()))
+            .max()
+    }
+}
+
+/// Matches names which contain the query, ignoring case and diacritics according to a locale's
+/// collation rules.
+#[cfg(feature = "icu_collator")]
+pub struct Collated {
+    collator: icu_collator::Collator,
+}
+
+#[cfg(feature = "icu_collator")]
+impl Collated {
+    /// A matcher for names in `locale`.
+    ///
+    /// # Errors
+    ///
+    /// If there is no collation data for `locale`.
+    pub fn new(locale: &icu_locid::Locale) -> Result<Self, icu_collator::CollatorError> {
+        let mut options = icu_collator::CollatorOptions::new();
+        // primary differences only: base letters, not accents or case
+        options.strength = Some(icu_collator::Strength::Primary);
+        Ok(Collated {
+            collator: icu_collator::Collator::try_new(&locale.into(), options)?,
+        })
+    }
+}
+
+#[cfg(feature = "icu_collator")]
+impl NameMatcher for Collated {
+    type Query = (String, usize);
+    fn prepare(&self, query: &str) -> (String, usize) {
+        (query.to_string(), query.chars().count())
+    }
+    /// Compares every substring of up to twice the query's length, since a folded character
+    /// may be written with more than one (e.g., "ß" and "ss", or decomposed accents).
+    fn quality(&self, name: &str, (query, len): &(String, usize)) -> Option<MatchQuality> {
+        if query.is_empty() {
+            return Some(MatchQuality::at(name, 0, 0));
+        }
+        name.char_indices()
+            .filter_map(|(start, _)| {
+                name[start..]
+                    .char_indices()
+                    .skip(1)
+                    .map(|(i, _)| start + i)
+                    .chain(std::iter::once(name.len()))
+                    .take(2 * len)
+                    .filter(|end| self.collator.compare(&name[start..*end], query).is_eq())
+                    .map(|end| MatchQuality::at(name, start, end))
+                    .max()
+            })
+            .max()
+    }
+}
+
+/// A node found by [`Tree::search_ranked`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    /// The node.
+    pub node: NodeId,
+    /// The node's role.
+    pub role: Role,
+    /// The node's index in document order.
+    pub position: usize,
+    /// How many nodes are in the tree.
+    pub nodes: usize,
+    /// How well the node's name matched.
+    pub quality: MatchQuality,
+}
+
+/// Scores hits for ranking; higher scores come first, and ties stay in document order.
+pub trait Scorer {
+    /// The score of `hit`.
+    fn score(&self, hit: &Hit) -> i64;
+}
+
+impl<F: Fn(&Hit) -> i64> Scorer for F {
+    fn score(&self, hit: &Hit) -> i64 {
+        self(hit)
+    }
+}
+
+/// Scores a hit as a weighted sum of its [`MatchQuality`], whether its role is a priority, and
+/// how far through the document it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weighted {
+    /// Roles to rank first, all else being equal.
+    pub priority: RoleSet,
+    /// Points per step of [`MatchQuality`].
+    pub quality: i64,
+    /// Points for having one of [`Weighted::priority`].
+    pub role: i64,
+    /// Points lost from the start of the document to the end.
+    pub position: i64,
+}
+
+impl Default for Weighted {
+    /// Headings and landmarks first, unless another node matches at least two steps better.
+    fn default() -> Self {
+        Weighted {
+            priority: RoleSet::EMPTY | Role::Heading | Role::Landmark,
+            quality: 100,
+            role: 150,
+            position: 50,
+        }
+    }
+}
+
+impl Scorer for Weighted {
+    fn score(&self, hit: &Hit) -> i64 {
+        let fraction = |weight: i64| {
+            let position = i64::try_from(hit.position).unwrap_or(i64::MAX);
+            let nodes = i64::try_from(hit.nodes).unwrap_or(i64::MAX).max(1);
+            weight.saturating_mul(position) / nodes
+        };
+        self.quality * hit.quality as i64
+            + if self.priority.contains(hit.role.into()) {
+                self.role
+            } else {
+                0
+            }
+            - fraction(self.position)
+    }
+}
+
+impl Tree {
+    /// Returns an [`Iterator`] over all nodes whose name matches `query`, in document order.
+    ///
+    /// ```
+    /// use a11y_tree_core::search::CaseInsensitive;
+    /// use a11y_tree_core::{Tree, TreeTraversal};
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Button", "name": "Save", "children": []},
+    /// #     {"role": "Button", "name": "Save As…", "children": []}
+    /// # ]}"#).unwrap();
+    /// let tree = Tree::from_root_node(root);
+    /// assert_eq!(tree.search_text("save", &CaseInsensitive).count(), 2);
+    /// ```
+    pub fn search_text<'a, M: NameMatcher>(
+        &'a self,
+        query: &str,
+        matcher: &'a M,
+    ) -> impl Iterator<Item = NodeId> + use<'a, M> {
+        let query = matcher.prepare(query);
+        // a tree without names has nothing to find
+        let nodes = (!self.names.is_empty()).then(|| self.root.descendants(&self.inner));
+        nodes.into_iter().flatten().filter(move |id| {
+            self.name(*id)
+                .is_some_and(|name| matcher.matches(name, &query))
+        })
+    }
+    /// Returns every node whose name matches `query`, best first according to `scorer`.
+    ///
+    /// ```
+    /// use a11y_tree_core::search::{CaseInsensitive, Weighted};
+    /// use a11y_tree_core::{Tree, TreeTraversal};
+    /// # let root = serde_json::from_str(r#"{"role": "Frame", "children": [
+    /// #     {"role": "Link", "name": "Downloads", "children": []},
+    /// #     {"role": "Heading", "name": "Downloads", "children": []}
+    /// # ]}"#).unwrap();
+    /// let tree = Tree::from_root_node(root);
+    /// let hits = tree.search_ranked("down", &CaseInsensitive, &Weighted::default());
+    /// assert_eq!(hits[0].role, atspi_common::Role::Heading);
+    /// ```
+    pub fn search_ranked<M: NameMatcher>(
+        &self,
+        query: &str,
+        matcher: &M,
+        scorer: &impl Scorer,
+    ) -> Vec<Hit> {
+        if self.names.is_empty() {
+            return Vec::new();
+        }
+        let query = matcher.prepare(query);
+        let nodes = self.nodes();
+        let mut hits = self
+            .root
+            .descendants(&self.inner)
+            .enumerate()
+            .filter_map(|(position, node)| {
+                let quality = matcher.quality(self.name(node)?, &query)?;
+                let hit = Hit {
+                    node,
+                    role: self.inner[node].get().role(),
+                    position,
+                    nodes,
+                    quality,
+                };
+                Some((scorer.score(&hit), hit))
+            })
+            .collect::<Vec<_>>();
+        // stable, so equal scores stay in document order
+        hits.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        hits.into_iter().map(|(_, hit)| hit).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "icu_collator")]
+    use super::Collated;
+    use super::{CaseInsensitive, Hit, Scorer, Weighted};
+    use crate::{Tree, TreeTraversal};
+
+    fn tree() -> Tree {
+        Tree::from_root_node(
+            serde_json::from_str(
+                r#"{"role": "Frame", "name": "Über uns", "children": [
+                    {"role": "Link", "name": "Straße", "children": []},
+                    {"role": "Link", "name": "Crème brûlée", "children": []}
+                ]}"#,
+            )
+            .expect("Valid JSON"),
+        )
+    }
+
+    #[test]
+    fn search_case_insensitive() {
+        let t = tree();
+        let found = |query| t.search_text(query, &CaseInsensitive).count();
+        assert_eq!(found("über"), 1);
+        assert_eq!(found("uber"), 0);
+        assert_eq!(found("CRÈME"), 1);
+        assert_eq!(found(""), 3);
+    }
+
+    #[test]
+    fn search_ranked() {
+        let t = Tree::from_root_node(
+            serde_json::from_str(
+                r#"{"role": "Frame", "children": [
+                    {"role": "Link", "name": "Open recent", "children": []},
+                    {"role": "Link", "name": "Reopen", "children": []},
+                    {"role": "Heading", "name": "Open", "children": []},
+                    {"role": "Button", "name": "Open", "children": []}
+                ]}"#,
+            )
+            .expect("Valid JSON"),
+        );
+        let ranked = |scorer: &dyn Fn(&Hit) -> i64| {
+            t.search_ranked("open", &CaseInsensitive, &scorer)
+                .into_iter()
+                .map(|hit| {
+                    t.name(hit.node).expect("Valid name").to_string() + " " + hit.role.name()
+                })
+                .collect::<Vec<_>>()
+        };
+        let weighted = Weighted::default();
+        assert_eq!(
+            ranked(&|hit| weighted.score(hit)),
+            [
+                "Open heading",
+                "Open button",
+                "Open recent link",
+                "Reopen link"
+            ]
+        );
+        // ties stay in document order
+        assert_eq!(
+            ranked(&|_| 0),
+            [
+                "Open recent link",
+                "Reopen link",
+                "Open heading",
+                "Open button"
+            ]
+        );
+    }
+
+    #[cfg(feature = "icu_collator")]
+    #[test]
+    fn search_collated() {
+        let t = tree();
+        let matcher = Collated::new(&"de".parse().expect("Valid locale")).expect("Valid collator");
+        let found = |query| t.search_text(query, &matcher).collect::<Vec<_>>();
+        assert_eq!(found("uber"), vec![t.root()]);
+        assert_eq!(found("strasse").len(), 1);
+        assert_eq!(found("BRULEE").len(), 1);
+        assert_eq!(found("xyz"), vec![]);
+    }
+}
@@ -0,0 +1,195 @@
+//! A desktop snapshot indexed per application, rather than as one tree.
+//!
+//! Each application (each child of the snapshot's root) is a [`Tree`] with its own indexes, so
+//! a change in one application only rebuilds that application's indexes, and queries over the
+//! whole desktop run on each application in parallel.
+//! A small router (each shard's unique roles) sends queries only to the shards which can answer
+//! them.
+
+use crate::{A11yNode, RoleSet, Tree, TreeConfig, TreeTraversal};
+use atspi_common::Role;
+use indextree::NodeId;
+use rayon::prelude::*;
+
+/// A desktop snapshot with one [`Tree`] (a shard) per application.
+#[derive(Debug)]
+pub struct ShardedTree {
+    /// The role of the snapshot's root, which is not in any shard.
+    root_role: Role,
+    config: TreeConfig,
+    shards: Vec<Tree>,
+    /// The unique roles in each shard.
+    router: Vec<RoleSet>,
+}
+
+impl ShardedTree {
+    /// Build one shard per child of `root_node`, each with the indexes in `config`, in parallel.
+    ///
+    /// ```
+    /// use a11y_tree_core::shard::ShardedTree;
+    /// use a11y_tree_core::TreeConfig;
+    /// use atspi_common::Role;
+    /// # let root = serde_json::from_str(r#"{"role": "DesktopFrame", "children": [
+    /// #     {"role": "Application", "children": [{"role": "Heading", "children": []}]},
+    /// #     {"role": "Application", "children": [{"role": "Link", "children": []}]}
+    /// # ]}"#).unwrap();
+    /// let desktop = ShardedTree::from_root_node_with(root, TreeConfig::default());
+    /// assert_eq!(desktop.shards().len(), 2);
+    /// assert_eq!(desktop.find_first(Role::Link).map(|(shard, _)| shard), Some(1));
+    /// assert_eq!(desktop.how_many(Role::Application), 2);
+    /// ```
+    #[must_use]
+    pub fn from_root_node_with(mut root_node: A11yNode, config: TreeConfig) -> Self {
+        let shards = std::mem::take(&mut root_node.children)
+            .into_par_iter()
+            .map(|app| Tree::from_root_node_with(app, config))
+            .collect::<Vec<_>>();
+        let mut sharded = ShardedTree {
+            root_role: root_node.role,
+            config,
+            router: vec![RoleSet::EMPTY; shards.len()],
+            shards,
+        };
+        for i in 0..sharded.shards.len() {
+            sharded.route(i);
+        }
+        sharded
+    }
+    /// Recompute the router's entry for shard `i`.
+    fn route(&mut self, i: usize) {
+        self.router[i] = self.shards[i].unique_roles_in(self.shards[i].root());
+    }
+    /// The role of the snapshot's root.
+    #[must_use]
+    pub fn root_role(&self) -> Role {
+        self.root_role
+    }
+    /// The shards, in the order of the applications in the snapshot.
+    #[must_use]
+    pub fn shards(&self) -> &[Tree] {
+        &self.shards
+    }
+    /// The unique roles in the whole snapshot, including its root.
+    #[must_use]
+    pub fn unique_roles(&self) -> RoleSet {
+        self.router
+            .iter()
+            .fold(RoleSet::from(self.root_role), |roles, shard| roles | *shard)
+    }
+    /// Replace application `i` with `app`, rebuilding only its shard's indexes; returns the old
+    /// shard.
+    ///
+    /// # Panics
+    ///
+    /// If there is no shard `i`.
+    pub fn replace_shard(&mut self, i: usize, app: A11yNode) -> Tree {
+        let old = std::mem::replace(
+            &mut self.shards[i],
+            Tree::from_root_node_with(app, self.config),
+        );
+        self.route(i);
+        old
+    }
+    /// Change shard `i` in place (e.g., with [`Tree::append_subtree`]), then update the router.
+    ///
+    /// # Panics
+    ///
+    /// If there is no shard `i`.
+    pub fn update_shard<R>(&mut self, i: usize, update: impl FnOnce(&mut Tree) -> R) -> R {
+        let result = update(&mut self.shards[i]);
+        self.route(i);
+        result
+    }
+    /// The first node with `role` in document order, as its shard's index and its [`NodeId`]
+    /// within that shard.
+    /// The snapshot's root is not in any shard, so it is never returned.
+    ///
+    /// Shards are searched in parallel, skipping those which the router rules out.
+    #[must_use]
+    pub fn find_first(&self, role: Role) -> Option<(usize, NodeId)> {
+        let rs = RoleSet::from(role);
+        self.shards
+            .par_iter()
+            .enumerate()
+            .filter(|(i, _)| self.router[*i].intersects(rs))
+            .find_map_first(|(i, shard)| {
+                let node = shard.find_first_cached(role)?;
+                shard.node_id(node).map(|id| (i, id))
+            })
+    }
+    /// The number of nodes with `role` in the whole snapshot, including its root.
+    ///
+    /// Shards are counted in parallel, skipping those which the router rules out.
+    #[must_use]
+    pub fn how_many(&self, role: Role) -> usize {
+        let rs = RoleSet::from(role);
+        let in_shards = self
+            .shards
+            .par_iter()
+            .enumerate()
+            .filter(|(i, _)| self.router[*i].intersects(rs))
+            .map(|(_, shard)| shard.how_many_roleset(role))
+            .sum::<usize>();
+        in_shards + usize::from(self.root_role == role)
+    }
+    /// The number of nodes in the whole snapshot, including its root.
+    #[must_use]
+    pub fn nodes(&self) -> usize {
+        1 + self.shards.iter().map(Tree::nodes).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedTree;
+    use crate::{A11yNode, Tree, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+
+    fn app(children: Vec<A11yNode>) -> A11yNode {
+        A11yNode {
+            role: Role::Application,
+            name: None,
+            toolkit: None,
+            children,
+        }
+    }
+
+    fn leaf(role: Role) -> A11yNode {
+        A11yNode {
+            role,
+            name: None,
+            toolkit: None,
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn sharded_matches_monolithic() {
+        let desktop = A11yNode {
+            role: Role::DesktopFrame,
+            name: None,
+            toolkit: None,
+            children: vec![
+                app(vec![leaf(Role::Heading), leaf(Role::Link)]),
+                app(vec![]),
+                app(vec![leaf(Role::Link), leaf(Role::Calendar)]),
+            ],
+        };
+        let whole = Tree::from_root_node_with(desktop.clone(), TreeConfig::default());
+        let mut sharded = ShardedTree::from_root_node_with(desktop, TreeConfig::default());
+        assert_eq!(sharded.nodes(), whole.nodes());
+        assert_eq!(sharded.unique_roles(), whole.unique_roles_roleset());
+        for role in whole.unique_roles().role_iter() {
+            assert_eq!(sharded.how_many(role), whole.how_many(role), "{role:?}");
+        }
+        assert_eq!(sharded.find_first(Role::Calendar).map(|(i, _)| i), Some(2));
+        assert_eq!(sharded.find_first(Role::DesktopFrame), None);
+        // only the changed shard is rebuilt, and the router follows it
+        let old = sharded.replace_shard(0, app(vec![leaf(Role::Calendar)]));
+        assert_eq!(old.how_many(Role::Heading), 1);
+        assert_eq!(sharded.how_many(Role::Heading), 0);
+        assert_eq!(sharded.find_first(Role::Calendar).map(|(i, _)| i), Some(0));
+        let heading = sharded.update_shard(1, |t| t.append_subtree(t.root(), leaf(Role::Heading)));
+        assert_eq!(sharded.find_first(Role::Heading), Some((1, heading)));
+    }
+}
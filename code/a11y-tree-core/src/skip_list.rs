@@ -0,0 +1,147 @@
+use crate::role_set::ROLE_BITS;
+use crate::{slot, HasRole, RoleSet};
+use atspi_common::Role;
+use indextree::{Arena, NodeId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// For each node, the next node in document order with a given role; only kept for a configured
+/// set of roles.
+///
+/// Each role's list is built the first time it is queried, and costs one pointer per arena slot;
+/// see [`SkipLists::bytes`].
+/// Mutating the tree invalidates all lists, which are then rebuilt on their next query.
+#[derive(Debug)]
+pub(crate) struct SkipLists {
+    roles: RoleSet,
+    /// Indexed by role, then by arena slot.
+    lists: Vec<OnceLock<Vec<Option<NodeId>>>>,
+    /// How many warm-ups are building lists in the background; see [`SkipLists::is_warming`].
+    warming: AtomicUsize,
+}
+
+impl Default for SkipLists {
+    fn default() -> Self {
+        SkipLists::new(RoleSet::EMPTY)
+    }
+}
+
+/// Only the roles are kept; the lists are built again on their next query.
+impl Serialize for SkipLists {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.roles.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for SkipLists {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RoleSet::deserialize(deserializer).map(SkipLists::new)
+    }
+}
+
+impl SkipLists {
+    pub(crate) fn new(roles: RoleSet) -> Self {
+        SkipLists {
+            roles,
+            lists: (0..ROLE_BITS).map(|_| OnceLock::new()).collect(),
+            warming: AtomicUsize::new(0),
+        }
+    }
+    /// Also keep lists for `roles`, without dropping any which are built.
+    pub(crate) fn add_roles(&mut self, roles: RoleSet) {
+        self.roles |= roles;
+    }
+    /// The roles which skip lists are kept for.
+    pub(crate) fn roles(&self) -> RoleSet {
+        self.roles
+    }
+    /// Stops keeping the lists for `roles`, dropping any which are built.
+    pub(crate) fn remove(&mut self, roles: RoleSet) {
+        self.roles &= !roles;
+        for role in roles.role_iter() {
+            if let Some(list) = self.lists.get_mut(role as usize) {
+                list.take();
+            }
+        }
+    }
+    /// Drops all built lists, so they are rebuilt from the current tree on their next query.
+    pub(crate) fn invalidate(&mut self) {
+        for list in &mut self.lists {
+            list.take();
+        }
+    }
+    /// Whether the list for `role` has been built.
+    pub(crate) fn is_built(&self, role: Role) -> bool {
+        self.lists
+            .get(role as usize)
+            .is_some_and(|list| list.get().is_some())
+    }
+    /// Whether a warm-up is running; until it builds a list, queries should fall back to a
+    /// traversal rather than build the list themselves.
+    pub(crate) fn is_warming(&self) -> bool {
+        self.warming.load(Ordering::Acquire) > 0
+    }
+    /// Marks the start (`true`) or end (`false`) of a warm-up.
+    pub(crate) fn set_warming(&self, warming: bool) {
+        if warming {
+            self.warming.fetch_add(1, Ordering::AcqRel);
+        } else {
+            // the lists may have been replaced (and the count reset) since the warm-up started
+            let _ = self
+                .warming
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        }
+    }
+    /// Builds the list for `role` from the tree rooted at `root`, if it is still kept and not
+    /// built yet; a warm-up may run after the role was removed.
+    pub(crate) fn warm<T: HasRole>(&self, arena: &Arena<T>, root: NodeId, role: Role) {
+        if !self.roles.contains(role.into()) {
+            return;
+        }
+        self.next(arena, root, root, role);
+    }
+    /// Returns the next node after `from` in document order with `role`.
+    /// The list for `role` is built from the tree rooted at `root` if needed.
+    ///
+    /// # Panics
+    ///
+    /// If `role` is not one of [`SkipLists::roles`].
+    pub(crate) fn next<T: HasRole>(
+        &self,
+        arena: &Arena<T>,
+        root: NodeId,
+        from: NodeId,
+        role: Role,
+    ) -> Option<NodeId> {
+        assert!(
+            self.roles.contains(role.into()),
+            "No skip list is kept for {role:?}"
+        );
+        let list = self.lists[role as usize].get_or_init(|| {
+            let mut list = vec![None; arena.len()];
+            let mut next = None;
+            // walk backwards, so `next` is always the closest following node with `role`
+            for id in root
+                .descendants(arena)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                list[slot(id)] = next;
+                if arena[id].get().role() == role {
+                    next = Some(id);
+                }
+            }
+            list
+        });
+        list[slot(from)]
+    }
+    /// Returns the memory used by the lists built so far, in bytes.
+    pub(crate) fn bytes(&self) -> usize {
+        self.lists
+            .iter()
+            .filter_map(OnceLock::get)
+            .map(|list| list.capacity() * size_of::<Option<NodeId>>())
+            .sum()
+    }
+}
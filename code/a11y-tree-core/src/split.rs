@@ -0,0 +1,295 @@
+//! Splitting a desktop snapshot into one file per application, and loading them back lazily.
+//!
+//! A screen reader caches each application separately, loading an application's tree only once
+//! the user interacts with it; this mirrors that layout on disk.
+//! Each child of the snapshot's root (usually a [`Role::Application`] under a
+//! [`Role::DesktopFrame`]) is written to its own file, and a [`Manifest`] lists them.
+
+use crate::limits::{self, Limits};
+use crate::{A11yNode, Tree, TreeConfig};
+use atspi_common::Role;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The name of the manifest file in a split snapshot's directory.
+pub const MANIFEST: &str = "manifest.json";
+
+/// The contents of a split snapshot's directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The role of the snapshot's root, which is not stored in any application's file.
+    pub root_role: Role,
+    /// One entry per application, in the order they appeared in the snapshot.
+    pub apps: Vec<ManifestEntry>,
+}
+
+/// An application in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The application's name and toolkit, as a file name (e.g., `firefox-gecko`), or `app-N` if
+    /// neither was recorded; unique within the manifest, and never `manifest`.
+    pub name: String,
+    /// The application's toolkit, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolkit: Option<String>,
+    /// The application's file, relative to the manifest.
+    pub file: String,
+    /// The role of the application's root node.
+    pub role: Role,
+    /// The number of nodes in the application's tree.
+    pub nodes: usize,
+}
+
+/// Split `root` into one tree per child, with a [`Manifest`] describing them.
+#[must_use]
+pub fn split(mut root: A11yNode) -> (Manifest, Vec<A11yNode>) {
+    // the manifest's own file is taken
+    let mut used = HashSet::from([MANIFEST.trim_end_matches(".json").to_string()]);
+    let apps = root
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, app)| {
+            let label = [app.name.as_deref(), app.toolkit.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut name = Some(file_name(&label))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("app-{i}"));
+            // a suffixed name may itself be taken, e.g., by an application named `x-2`
+            let base = name.clone();
+            let mut suffix = i;
+            while !used.insert(name.clone()) {
+                name = format!("{base}-{suffix}");
+                suffix += 1;
+            }
+            ManifestEntry {
+                file: format!("{name}.json"),
+                name,
+                toolkit: app.toolkit.clone(),
+                role: app.role,
+                nodes: count_nodes(app),
+            }
+        })
+        .collect();
+    let manifest = Manifest {
+        root_role: root.role,
+        apps,
+    };
+    (manifest, std::mem::take(&mut root.children))
+}
+
+/// Split `root` (see [`split`]) and write each application and the manifest to `dir`, which is
+/// created if needed.
+///
+/// # Errors
+///
+/// If `dir` or any file in it could not be written.
+pub fn write_split(root: A11yNode, dir: &Path) -> io::Result<Manifest> {
+    fs::create_dir_all(dir)?;
+    let (manifest, apps) = split(root);
+    for (entry, app) in manifest.apps.iter().zip(&apps) {
+        fs::write(dir.join(&entry.file), serde_json::to_string(app)?)?;
+    }
+    fs::write(dir.join(MANIFEST), serde_json::to_string_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// A split snapshot, whose applications are loaded the first time they are accessed.
+#[derive(Debug)]
+pub struct SplitSnapshot {
+    dir: PathBuf,
+    manifest: Manifest,
+    config: TreeConfig,
+    limits: Limits,
+    trees: Vec<OnceLock<Tree>>,
+}
+
+impl SplitSnapshot {
+    /// Read the manifest in `dir`; applications are built with the indexes in `config` when they
+    /// are loaded, and refused if they exceed `limits`.
+    ///
+    /// # Errors
+    ///
+    /// If the manifest could not be read or parsed.
+    pub fn open(dir: &Path, config: TreeConfig, limits: Limits) -> io::Result<Self> {
+        let manifest: Manifest = serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST))?)?;
+        Ok(SplitSnapshot {
+            dir: dir.to_path_buf(),
+            trees: manifest.apps.iter().map(|_| OnceLock::new()).collect(),
+            manifest,
+            config,
+            limits,
+        })
+    }
+    /// The snapshot's manifest.
+    #[must_use]
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+    /// Returns whether the application called `name` has been loaded.
+    #[must_use]
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|i| self.trees[i].get().is_some())
+    }
+    /// Returns the tree of the application called `name`, loading it if needed.
+    ///
+    /// # Errors
+    ///
+    /// If there is no such application, or its file could not be read or parsed, or exceeds the
+    /// limits given to [`SplitSnapshot::open`].
+    pub fn app(&self, name: &str) -> io::Result<&Tree> {
+        let i = self.position(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("No application: {name}"))
+        })?;
+        if let Some(tree) = self.trees[i].get() {
+            return Ok(tree);
+        }
+        let data = fs::read_to_string(self.dir.join(&self.manifest.apps[i].file))?;
+        let root = limits::from_json_str(&data, self.limits)?;
+        Ok(self.trees[i].get_or_init(|| Tree::from_root_node_with(root, self.config)))
+    }
+    fn position(&self, name: &str) -> Option<usize> {
+        self.manifest.apps.iter().position(|app| app.name == name)
+    }
+}
+
+/// A file name for an application name: lowercase, with anything but letters and digits replaced
+/// by `-`.
+fn file_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+fn count_nodes(node: &A11yNode) -> usize {
+    // an explicit stack, since an application's tree can be deeper than the call stack allows
+    let mut stack = vec![node];
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        count += 1;
+        stack.extend(&node.children);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_name, split, write_split, SplitSnapshot};
+    use crate::limits::Limits;
+    use crate::{A11yNode, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+
+    fn node(role: Role, name: Option<&str>, children: Vec<A11yNode>) -> A11yNode {
+        A11yNode {
+            role,
+            name: name.map(String::from),
+            toolkit: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn split_and_load() {
+        let app = |name| {
+            node(
+                Role::Application,
+                name,
+                vec![node(
+                    Role::Frame,
+                    None,
+                    vec![node(Role::Button, None, vec![])],
+                )],
+            )
+        };
+        let root = node(
+            Role::DesktopFrame,
+            None,
+            vec![
+                app(Some("Firefox Web Browser")),
+                app(None),
+                app(Some("firefox web-browser")),
+            ],
+        );
+        let (manifest, apps) = split(root.clone());
+        let names = manifest
+            .apps
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["firefox-web-browser", "app-1", "firefox-web-browser-2"]
+        );
+        assert_eq!(apps.len(), 3);
+        assert!(manifest.apps.iter().all(|app| app.nodes == 3));
+        let (taken, _) = split(node(
+            Role::DesktopFrame,
+            None,
+            vec![app(Some("x-2")), app(Some("x")), app(Some("x"))],
+        ));
+        let names = taken
+            .apps
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x-2", "x", "x-3"]);
+        let gtk = |name| {
+            let mut app = app(name);
+            app.toolkit = Some("GTK".to_string());
+            app
+        };
+        let desktop = node(
+            Role::DesktopFrame,
+            None,
+            vec![app(Some("Manifest")), gtk(Some("Text Editor")), gtk(None)],
+        );
+        let (reserved, _) = split(desktop.clone());
+        let names = reserved
+            .apps
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<Vec<_>>();
+        // an application named `manifest` would overwrite the manifest's file
+        assert_eq!(names, ["manifest-0", "text-editor-gtk", "gtk"]);
+        assert_eq!(reserved.apps[1].toolkit.as_deref(), Some("GTK"));
+        let dir = std::env::temp_dir().join(format!("split-manifest-{}", std::process::id()));
+        write_split(desktop, &dir).expect("Able to write snapshot");
+        let snapshot =
+            SplitSnapshot::open(&dir, TreeConfig::default(), Limits::NONE).expect("Valid manifest");
+        assert_eq!(snapshot.manifest(), &reserved);
+        assert!(snapshot.app("manifest-0").is_ok());
+        std::fs::remove_dir_all(&dir).expect("Able to clean up");
+
+        let dir = std::env::temp_dir().join(format!("split-snapshot-{}", std::process::id()));
+        write_split(root, &dir).expect("Able to write snapshot");
+        let snapshot =
+            SplitSnapshot::open(&dir, TreeConfig::default(), Limits::NONE).expect("Valid manifest");
+        assert_eq!(snapshot.manifest(), &manifest);
+        assert!(!snapshot.is_loaded("app-1"));
+        let tree = snapshot.app("app-1").expect("Valid application");
+        assert_eq!(tree.how_many_roleset(Role::Button), 1);
+        assert!(snapshot.is_loaded("app-1"));
+        assert!(!snapshot.is_loaded("firefox-web-browser"));
+        assert!(snapshot.app("missing").is_err());
+        let tight = Limits {
+            max_nodes: 2,
+            ..Limits::NONE
+        };
+        let snapshot =
+            SplitSnapshot::open(&dir, TreeConfig::default(), tight).expect("Valid manifest");
+        assert!(snapshot.app("app-1").is_err());
+        assert!(!snapshot.is_loaded("app-1"));
+        std::fs::remove_dir_all(&dir).expect("Able to clean up");
+        assert_eq!(file_name("  Text Editor (2) "), "text-editor-2");
+    }
+}
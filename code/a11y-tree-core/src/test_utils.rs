@@ -0,0 +1,459 @@
+//! Assertion helpers for comparing tree backends and method variants.
+//!
+//! Enabled with the `test-utils` feature.
+//! Failures describe nodes by their role and path from the root (see [`TreeTraversal::path`])
+//! instead of dumping whole arena nodes, so that conformance failures are diagnosable.
+
+use crate::{EmptinessPolicy, HasRole, RoleSet, TreeTraversal};
+use atspi_common::Role;
+use indextree::NodeId;
+use rayon::iter::ParallelIterator;
+use std::collections::HashMap;
+
+/// How to describe a method's result when an assertion fails.
+///
+/// Collections are described one line per item, so that the first differing item can be
+/// pointed out.
+pub trait Describe<T: TreeTraversal> {
+    /// Describe the value, one line per item.
+    fn describe(&self, tree: &T) -> Vec<String>;
+}
+
+impl<T: TreeTraversal> Describe<T> for indextree::Node<T::Node> {
+    fn describe(&self, tree: &T) -> Vec<String> {
+        let path = tree.node_id(self).map(|id| tree.path(id));
+        vec![format!("{} at path {path:?}", self.get().role())]
+    }
+}
+
+impl<T: TreeTraversal, X: Describe<T>> Describe<T> for &X {
+    fn describe(&self, tree: &T) -> Vec<String> {
+        (*self).describe(tree)
+    }
+}
+
+impl<T: TreeTraversal, X: Describe<T>> Describe<T> for Option<X> {
+    fn describe(&self, tree: &T) -> Vec<String> {
+        match self {
+            Some(x) => x.describe(tree),
+            None => vec!["None".to_string()],
+        }
+    }
+}
+
+impl<T: TreeTraversal, X: Describe<T>> Describe<T> for Vec<X> {
+    fn describe(&self, tree: &T) -> Vec<String> {
+        self.iter().flat_map(|x| x.describe(tree)).collect()
+    }
+}
+
+impl<T: TreeTraversal, X: Describe<T>, Y: Describe<T>> Describe<T> for (X, Y) {
+    fn describe(&self, tree: &T) -> Vec<String> {
+        vec![format!(
+            "({}, {})",
+            self.0.describe(tree).join(", "),
+            self.1.describe(tree).join(", ")
+        )]
+    }
+}
+
+impl<T: TreeTraversal> Describe<T> for RoleSet {
+    fn describe(&self, _tree: &T) -> Vec<String> {
+        vec![format!("{self:#}")]
+    }
+}
+
+macro_rules! describe_display {
+    ($($ty:ty),*) => {
+        $(
+            impl<T: TreeTraversal> Describe<T> for $ty {
+                fn describe(&self, _tree: &T) -> Vec<String> {
+                    vec![self.to_string()]
+                }
+            }
+        )*
+    };
+}
+describe_display!(Role, usize, bool);
+
+/// Describe the difference between two results, pointing out the first differing item.
+///
+/// `expected` is described in the context of `expected_tree`, and `actual` in the context of
+/// `actual_tree`.
+pub fn diff<A, B, X, Y>(expected_tree: &A, expected: &X, actual_tree: &B, actual: &Y) -> String
+where
+    A: TreeTraversal,
+    B: TreeTraversal,
+    X: Describe<A>,
+    Y: Describe<B>,
+{
+    let expected = expected.describe(expected_tree);
+    let actual = actual.describe(actual_tree);
+    let first = expected
+        .iter()
+        .zip(&actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.len().min(actual.len()));
+    let item = |lines: &[String]| {
+        lines
+            .get(first)
+            .map_or("<missing>", String::as_str)
+            .to_string()
+    };
+    if expected.len() == 1 && actual.len() == 1 {
+        return format!("expected: {}\n  actual: {}", expected[0], actual[0]);
+    }
+    format!(
+        "first difference at item {first} (expected {} items, got {}):\nexpected: {}\n  actual: {}",
+        expected.len(),
+        actual.len(),
+        item(&expected),
+        item(&actual),
+    )
+}
+
+/// Compare two trees node by node in document order, returning a description of the first
+/// node which differs in role or number of children.
+pub fn tree_diff<A: TreeTraversal, B: TreeTraversal>(expected: &A, actual: &B) -> Option<String> {
+    let mut expected_ids = expected.root().descendants(expected.arena());
+    let mut actual_ids = actual.root().descendants(actual.arena());
+    loop {
+        match (expected_ids.next(), actual_ids.next()) {
+            (None, None) => return None,
+            (Some(e), Some(a)) => {
+                let e_role = expected.arena()[e].get().role();
+                let a_role = actual.arena()[a].get().role();
+                let e_children = e.children(expected.arena()).count();
+                let a_children = a.children(actual.arena()).count();
+                if e_role != a_role || e_children != a_children {
+                    return Some(format!(
+                        "trees differ at path {:?}:\nexpected: {e_role} with {e_children} children\n  actual: {a_role} with {a_children} children",
+                        expected.path(e),
+                    ));
+                }
+            }
+            (Some(e), None) => {
+                return Some(format!(
+                    "actual tree ends early; expected a node at path {:?}",
+                    expected.path(e)
+                ))
+            }
+            (None, Some(a)) => {
+                return Some(format!(
+                    "actual tree has extra nodes, starting at path {:?}",
+                    actual.path(a)
+                ))
+            }
+        }
+    }
+}
+
+/// Assert that two trees (possibly of different backends) have the same structure: the same
+/// roles, in the same shape.
+///
+/// On failure, the path to the first differing node is shown.
+#[macro_export]
+macro_rules! assert_tree_eq {
+    ($expected:expr, $actual:expr $(,)?) => {{
+        if let Some(diff) = $crate::test_utils::tree_diff($expected, $actual) {
+            panic!(
+                "{} != {}: {}",
+                std::any::type_name_of_val($expected),
+                std::any::type_name_of_val($actual),
+                diff
+            );
+        }
+    }};
+}
+
+/// Assert that two methods give the same result.
+///
+/// The methods may be called on the same tree, or on different trees (to compare backends).
+/// Any further arguments are passed to both methods.
+/// Iterators can be compared by adding `collect` before the first tree.
+///
+/// ```ignore
+/// assert_same_result!(tree => find_first, tree => find_first_roleset, Role::Heading);
+/// assert_same_result!(tree => how_many, tree_count => how_many_roleset, Role::Link);
+/// assert_same_result!(collect tree => iter_leafs, tree => par_iter_leafs);
+/// ```
+#[macro_export]
+macro_rules! assert_same_result {
+    (collect $t1:expr => $fn1:ident, $t2:expr => $fn2:ident $(, $arg:expr)* $(,)?) => {{
+        let (t1, t2) = ($t1, $t2);
+        let expected = t1.$fn1($($arg),*).collect::<Vec<_>>();
+        let actual = t2.$fn2($($arg),*).collect::<Vec<_>>();
+        $crate::assert_same_result!(@check t1, $fn1, expected, t2, $fn2, actual $(, $arg)*);
+    }};
+    ($t1:expr => $fn1:ident, $t2:expr => $fn2:ident $(, $arg:expr)* $(,)?) => {{
+        let (t1, t2) = ($t1, $t2);
+        let expected = t1.$fn1($($arg),*);
+        let actual = t2.$fn2($($arg),*);
+        $crate::assert_same_result!(@check t1, $fn1, expected, t2, $fn2, actual $(, $arg)*);
+    }};
+    (@check $t1:ident, $fn1:ident, $expected:ident, $t2:ident, $fn2:ident, $actual:ident $(, $arg:expr)*) => {
+        if $expected != $actual {
+            let args: Vec<String> = vec![$(format!("{:?}", $arg)),*];
+            panic!(
+                "{}::{} != {}::{} ({})\n{}",
+                std::any::type_name_of_val($t1),
+                stringify!($fn1),
+                std::any::type_name_of_val($t2),
+                stringify!($fn2),
+                args.join(", "),
+                $crate::test_utils::diff($t1, &$expected, $t2, &$actual),
+            );
+        }
+    };
+}
+
+/// Check the laws relating a backend's method variants: every variant of a query (e.g.,
+/// `how_many`, `how_many_roleset`, `par_how_many`) must give the same result as the plain
+/// traversal, for every role.
+///
+/// Subtree queries are checked on a sample of at most about [`LAW_SAMPLE`] nodes.
+/// `par_iter_leafs` goes in arena order, which may not be the document order after mutation (see
+/// [`TreeTraversal::arena_in_document_order`]); if so, it is checked regardless of order.
+///
+/// # Panics
+///
+/// If any law does not hold; the message names the law and its arguments.
+pub fn check_laws<T: TreeTraversal>(t: &T) {
+    let laws = Laws::new(t);
+    laws.whole_tree();
+    laws.per_role();
+    laws.role_sets();
+    laws.subtrees();
+}
+
+/// About how many nodes [`check_laws`] checks subtree queries for.
+pub const LAW_SAMPLE: usize = 1000;
+
+/// A tree, and the expected results of its queries, computed by plain traversal.
+struct Laws<'a, T: TreeTraversal> {
+    t: &'a T,
+    /// Every node, in document order.
+    ids: Vec<NodeId>,
+    /// The roles in each node's subtree.
+    subtree: HashMap<NodeId, RoleSet>,
+    /// Whether arena order is document order.
+    in_arena_order: bool,
+}
+
+impl<'a, T: TreeTraversal> Laws<'a, T> {
+    fn new(t: &'a T) -> Self {
+        let arena = t.arena();
+        let ids = t.root().descendants(arena).collect::<Vec<_>>();
+        assert_eq!(t.nodes(), ids.len(), "nodes == descendants(root).count()");
+        let in_arena_order = arena
+            .iter()
+            .filter(|node| !node.is_removed())
+            .map(|node| t.node_id(node))
+            .eq(ids.iter().map(|id| Some(*id)));
+        assert!(
+            in_arena_order || !t.arena_in_document_order(),
+            "arena_in_document_order implies arena order == descendants(root) order"
+        );
+        // children come after their parent in `ids`
+        let mut subtree = HashMap::with_capacity(ids.len());
+        for id in ids.iter().rev() {
+            let roles = id
+                .children(arena)
+                .fold(RoleSet::from(arena[*id].get().role()), |rs, child| {
+                    rs | subtree[&child]
+                });
+            subtree.insert(*id, roles);
+        }
+        Laws {
+            t,
+            ids,
+            subtree,
+            in_arena_order,
+        }
+    }
+
+    fn id(&self, node: Option<&indextree::Node<T::Node>>) -> Option<NodeId> {
+        node.and_then(|node| self.t.node_id(node))
+    }
+
+    fn ids<'n>(
+        &self,
+        nodes: impl IntoIterator<Item = &'n indextree::Node<T::Node>>,
+    ) -> Vec<Option<NodeId>>
+    where
+        T::Node: 'n,
+    {
+        nodes.into_iter().map(|node| self.id(Some(node))).collect()
+    }
+
+    fn whole_tree(&self) {
+        let t = self.t;
+        let mut leafs = self.ids(t.iter_leafs());
+        let mut par_leafs = self.ids(t.par_iter_leafs().collect::<Vec<_>>());
+        if !self.in_arena_order {
+            leafs.sort_unstable();
+            par_leafs.sort_unstable();
+        }
+        assert_eq!(leafs, par_leafs, "iter_leafs == par_iter_leafs");
+        assert_eq!(
+            t.max_depth(),
+            t.par_max_depth(),
+            "max_depth == par_max_depth"
+        );
+        let unique = t.unique_roles();
+        assert_eq!(
+            unique,
+            t.par_unique_roles(),
+            "unique_roles == par_unique_roles"
+        );
+        assert_eq!(
+            unique,
+            t.unique_roles_roleset(),
+            "unique_roles == unique_roles_roleset"
+        );
+        assert_eq!(
+            unique.role_iter().collect::<Vec<_>>(),
+            t.unique_roles_sorted(),
+            "unique_roles == unique_roles_sorted"
+        );
+        let mut first = t.unique_roles_first_occurrence();
+        first.sort_by_key(|role| *role as u32);
+        assert_eq!(
+            t.unique_roles_sorted(),
+            first,
+            "unique_roles_sorted == sorted(unique_roles_first_occurrence)"
+        );
+    }
+
+    fn per_role(&self) {
+        let (t, arena) = (self.t, self.t.arena());
+        let counts = t.role_counts_in(t.root());
+        for role in RoleSet::ALL.role_iter() {
+            let expected = self.id(t.find_first(role));
+            for (law, actual) in [
+                ("par_find_first", t.par_find_first(role)),
+                ("find_first_roleset", t.find_first_roleset(role)),
+                ("par_find_first_roleset", t.par_find_first_roleset(role)),
+                ("find_first_stack", t.find_first_stack(role)),
+                ("find_first_cached", t.find_first_cached(role)),
+            ] {
+                assert_eq!(expected, self.id(actual), "find_first == {law} ({role})");
+            }
+            let matches = self
+                .ids
+                .iter()
+                .copied()
+                .filter(|id| arena[*id].get().role() == role)
+                .collect::<Vec<_>>();
+            for (law, actual) in [
+                ("how_many", t.how_many(role)),
+                ("par_how_many", t.par_how_many(role)),
+                ("how_many_roleset", t.how_many_roleset(role)),
+                ("par_how_many_roleset", t.par_how_many_roleset(role)),
+                ("how_many_in(root)", t.how_many_in(t.root(), role)),
+                (
+                    "role_counts_in(root)",
+                    counts
+                        .iter()
+                        .find(|(r, _)| *r == role)
+                        .map_or(0, |(_, count)| *count),
+                ),
+            ] {
+                assert_eq!(matches.len(), actual, "matches.count() == {law} ({role})");
+            }
+            // the root is where the walk starts, so it is never found
+            let expected = matches
+                .into_iter()
+                .filter(|id| *id != t.root())
+                .collect::<Vec<_>>();
+            for (law, cached) in [("find_next", false), ("find_next_cached", true)] {
+                let walked = std::iter::successors(Some(t.root()), |from| {
+                    self.id(if cached {
+                        t.find_next_cached(*from, role)
+                    } else {
+                        t.find_next(*from, role)
+                    })
+                })
+                .skip(1)
+                .collect::<Vec<_>>();
+                assert_eq!(expected, walked, "matches == {law} walk ({role})");
+            }
+        }
+    }
+
+    fn role_sets(&self) {
+        let t = self.t;
+        let common = RoleSet::EMPTY | Role::Section | Role::Static | Role::Paragraph | Role::Link;
+        for roles in [
+            common,
+            t.unique_roles(),
+            RoleSet::EMPTY,
+            Role::Heading.into(),
+        ] {
+            let without = self.ids(t.iter_without(roles));
+            assert_eq!(
+                without,
+                self.ids(t.iter_without_roleset(roles)),
+                "iter_without == iter_without_roleset ({roles})"
+            );
+            assert_eq!(
+                without.len(),
+                t.nodes() - roles.role_iter().map(|r| t.how_many(r)).sum::<usize>(),
+                "iter_without.count() == nodes - how_many ({roles})"
+            );
+            let lacking = self
+                .ids
+                .iter()
+                .filter(|id| !self.subtree[*id].intersects(roles))
+                .map(|id| Some(*id))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                lacking,
+                self.ids(t.iter_lacking(roles)),
+                "lacking nodes == iter_lacking ({roles})"
+            );
+        }
+        assert!(
+            !t.subtree_contains(t.root(), RoleSet::EMPTY),
+            "!subtree_contains(root, EMPTY)"
+        );
+    }
+
+    fn subtrees(&self) {
+        let t = self.t;
+        for node in self
+            .ids
+            .iter()
+            .step_by((self.ids.len() / LAW_SAMPLE).max(1))
+        {
+            let unique = self.subtree[node];
+            assert_eq!(
+                unique,
+                t.unique_roles_in(*node),
+                "descendants roles == unique_roles_in ({node:?})"
+            );
+            for (role, count) in t.role_counts_in(*node) {
+                assert_eq!(
+                    count,
+                    t.how_many_in(*node, role),
+                    "role_counts_in == how_many_in ({node:?}, {role})"
+                );
+            }
+            for policy in [
+                EmptinessPolicy::default(),
+                EmptinessPolicy::INTERACTIVE_ONLY,
+            ] {
+                assert_eq!(
+                    !unique.intersects(policy.content),
+                    t.is_effectively_empty(*node, policy),
+                    "is_effectively_empty == !subtree roles intersect content ({node:?})"
+                );
+                assert_eq!(
+                    unique.intersects(policy.content),
+                    t.subtree_contains(*node, policy.content),
+                    "subtree_contains == subtree roles intersect content ({node:?})"
+                );
+            }
+        }
+    }
+}
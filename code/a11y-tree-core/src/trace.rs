@@ -0,0 +1,190 @@
+//! Recorded tree mutations, and per-second summaries of them for plotting.
+//!
+//! Accessibility trees are written far more often than they are read, so the shape of real write
+//! loads (how bursty they are, how large the affected subtrees are, which roles they touch)
+//! decides how a write-optimized index should work.
+
+use crate::{RoleSet, Tree, TreeTraversal};
+use atspi_common::Role;
+use indextree::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The kind of a [`MutationEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationKind {
+    /// A subtree was added.
+    Insert,
+    /// A subtree was removed.
+    Remove,
+    /// A node's properties changed, without changing the tree's shape.
+    Update,
+}
+
+/// A single change to a tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MutationEvent {
+    /// When the change happened, in milliseconds since the trace started.
+    pub time_ms: u64,
+    /// What kind of change it was.
+    pub kind: MutationKind,
+    /// The path to the changed node (see [`TreeTraversal::path`]).
+    pub path: Vec<usize>,
+    /// The number of nodes in the changed subtree.
+    pub nodes: usize,
+    /// The roles in the changed subtree.
+    pub roles: RoleSet,
+}
+
+impl MutationEvent {
+    /// Describe a change to the subtree rooted at `node`.
+    /// For a removal, call this before the subtree is removed.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not a valid ID in `tree`.
+    #[must_use]
+    pub fn new(tree: &Tree, node: NodeId, kind: MutationKind, time_ms: u64) -> Self {
+        let (nodes, roles) = match kind {
+            MutationKind::Update => (1, tree.arena()[node].get().role.into()),
+            MutationKind::Insert | MutationKind::Remove => (
+                node.descendants(tree.arena()).count(),
+                tree.unique_roles_in(node),
+            ),
+        };
+        MutationEvent {
+            time_ms,
+            kind,
+            path: tree.path(node),
+            nodes,
+            roles,
+        }
+    }
+}
+
+/// A summary of the [`MutationEvent`]s in one second of a trace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineSecond {
+    /// Seconds since the trace started.
+    pub second: u64,
+    /// Number of [`MutationKind::Insert`] events.
+    pub inserts: usize,
+    /// Number of [`MutationKind::Remove`] events.
+    pub removes: usize,
+    /// Number of [`MutationKind::Update`] events.
+    pub updates: usize,
+    /// Total number of nodes in all changed subtrees.
+    pub nodes: usize,
+    /// The largest changed subtree.
+    pub max_nodes: usize,
+    /// The roles in any changed subtree, in ascending numeric order.
+    pub roles: Vec<Role>,
+}
+
+/// Summarize `events` per second, including seconds with no events, up to the last event.
+#[must_use]
+pub fn timeline(events: &[MutationEvent]) -> Vec<TimelineSecond> {
+    let mut seconds: BTreeMap<u64, (TimelineSecond, RoleSet)> = BTreeMap::new();
+    for event in events {
+        let (second, roles) = seconds.entry(event.time_ms / 1000).or_default();
+        match event.kind {
+            MutationKind::Insert => second.inserts += 1,
+            MutationKind::Remove => second.removes += 1,
+            MutationKind::Update => second.updates += 1,
+        }
+        second.nodes += event.nodes;
+        second.max_nodes = second.max_nodes.max(event.nodes);
+        *roles |= event.roles;
+    }
+    let Some(last) = seconds.keys().next_back().copied() else {
+        return Vec::new();
+    };
+    (0..=last)
+        .map(|i| {
+            let (second, roles) = seconds.remove(&i).unwrap_or_default();
+            TimelineSecond {
+                second: i,
+                roles: roles.role_iter().collect(),
+                ..second
+            }
+        })
+        .collect()
+}
+
+/// Format a timeline as CSV, with roles separated by `;`.
+#[must_use]
+pub fn timeline_csv(timeline: &[TimelineSecond]) -> String {
+    let mut out = String::from("second,inserts,removes,updates,nodes,max_nodes,roles\n");
+    for second in timeline {
+        let roles = second
+            .roles
+            .iter()
+            .map(Role::name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{roles}",
+            second.second,
+            second.inserts,
+            second.removes,
+            second.updates,
+            second.nodes,
+            second.max_nodes,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{timeline, timeline_csv, MutationEvent, MutationKind};
+    use crate::{Tree, TreeConfig, TreeTraversal};
+    use atspi_common::Role;
+
+    #[test]
+    fn per_second_summary() {
+        let root = serde_json::from_str(
+            r#"{"role": "Frame", "children": [
+                {"role": "List", "children": [{"role": "ListItem", "children": []}]}
+            ]}"#,
+        )
+        .expect("Valid JSON");
+        let list_json = r#"{"role": "List", "children": [
+            {"role": "ListItem", "children": []},
+            {"role": "ListItem", "children": []}
+        ]}"#;
+        let mut tree = Tree::from_root_node_with(root, TreeConfig::default());
+        let list = tree.arena()[tree.root()].first_child().expect("Has a list");
+        let mut events = vec![MutationEvent::new(&tree, list, MutationKind::Remove, 200)];
+        tree.remove_subtree(list);
+        let added = tree.append_subtree(
+            tree.root(),
+            serde_json::from_str(list_json).expect("Valid JSON"),
+        );
+        events.push(MutationEvent::new(&tree, added, MutationKind::Insert, 900));
+        events.push(MutationEvent::new(
+            &tree,
+            tree.root(),
+            MutationKind::Update,
+            2500,
+        ));
+
+        let timeline = timeline(&events);
+        assert_eq!(timeline.len(), 3);
+        assert_eq!((timeline[0].inserts, timeline[0].removes), (1, 1));
+        assert_eq!((timeline[0].nodes, timeline[0].max_nodes), (5, 3));
+        assert_eq!(timeline[0].roles, [Role::List, Role::ListItem]);
+        assert_eq!(timeline[1].nodes, 0);
+        assert_eq!(
+            (timeline[2].updates, &*timeline[2].roles),
+            (1, &[Role::Frame][..])
+        );
+        assert_eq!(
+            timeline_csv(&timeline).lines().nth(1),
+            Some("0,1,1,0,5,3,list;list item")
+        );
+    }
+}
@@ -0,0 +1,742 @@
+use crate::flags::{FlagWord, Flags};
+use crate::lazy::LazyTree;
+use crate::test_utils::check_laws;
+use crate::{
+    assert_same_result, assert_tree_eq, A11yNode, HasRole, Index, Node, QueryPlan, RoleSet, Tree,
+    TreeConfig, TreeCount, TreeTraversal,
+};
+use atspi_common::Role;
+
+use std::fs;
+use std::sync::OnceLock;
+
+const REAL_FN: &str = "../../data/single-page-html-spec.json";
+
+static REAL_JSON: OnceLock<String> = OnceLock::new();
+static REAL_TREE_NODES: OnceLock<A11yNode> = OnceLock::new();
+static REAL_TREE: OnceLock<Tree> = OnceLock::new();
+static REAL_TREE_COUNT: OnceLock<TreeCount> = OnceLock::new();
+
+fn real_data() -> &'static String {
+    REAL_JSON.get_or_init(|| fs::read_to_string(REAL_FN).expect("Able to read file!"))
+}
+fn real_tree_nodes() -> &'static A11yNode {
+    let data = real_data();
+    REAL_TREE_NODES.get_or_init(|| serde_json::from_str(data).expect("Valid JSON!"))
+}
+fn real_tree() -> &'static Tree {
+    let root_node = real_tree_nodes();
+    REAL_TREE.get_or_init(|| {
+        let mut t = Tree::from_root_node(root_node.clone());
+        t.build_rolesets();
+        t.set_skip_lists(t.unique_roles_roleset());
+        t
+    })
+}
+fn real_tree_count() -> &'static TreeCount {
+    let root_node = real_tree_nodes();
+    REAL_TREE_COUNT.get_or_init(|| {
+        let mut tc = TreeCount::from_root_node(root_node.clone());
+        tc.build_rolesets();
+        tc
+    })
+}
+
+fn fresh_lazy_tree() -> LazyTree {
+    let mut t = LazyTree::from_root_node(real_tree_nodes().clone());
+    t.build_rolesets();
+    t
+}
+
+#[test]
+fn laws_tree() {
+    check_laws(real_tree());
+}
+
+#[test]
+fn laws_tree_count() {
+    check_laws(real_tree_count());
+}
+
+#[test]
+fn laws_tree_unindexed() {
+    check_laws(&Tree::from_root_node(real_tree_nodes().clone()));
+    check_laws(&TreeCount::from_root_node(real_tree_nodes().clone()));
+}
+
+#[test]
+fn laws_tree_all_indexes() {
+    check_laws(&Tree::from_root_node_with(
+        real_tree_nodes().clone(),
+        TreeConfig::ALL,
+    ));
+}
+
+#[test]
+fn laws_lazy_tree() {
+    let mut t = fresh_lazy_tree();
+    check_laws(&t);
+    // appended to the root, so the new nodes are last in both arena and document order
+    let calendar = A11yNode {
+        role: Role::Calendar,
+        name: None,
+        toolkit: None,
+        children: vec![],
+    };
+    t.append_subtree(t.root(), calendar);
+    assert!(t.stale_nodes() > 0);
+    check_laws(&t);
+}
+
+#[test]
+fn validate_unique_roles_order() {
+    let rt = real_tree();
+    let first = rt.unique_roles_first_occurrence();
+    // the first occurrence of each role must appear in strictly increasing document order
+    let positions = rt
+        .root
+        .descendants(&rt.inner)
+        .enumerate()
+        .map(|(i, id)| (id, i))
+        .collect::<std::collections::HashMap<_, _>>();
+    let first_positions = first
+        .iter()
+        .map(|role| {
+            let node = rt.find_first(*role).expect("Role is in tree");
+            let id = rt.inner.get_node_id(node).expect("Node is in arena");
+            positions[&id]
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        first_positions.windows(2).all(|w| w[0] < w[1]),
+        "unique_roles_first_occurrence is not in document order: {first:?}"
+    );
+    let sorted = rt.unique_roles_sorted();
+    assert!(
+        sorted.windows(2).all(|w| (w[0] as u32) < (w[1] as u32)),
+        "unique_roles_sorted is not in ascending numeric order: {sorted:?}"
+    );
+}
+
+#[test]
+fn validate_backends_agree() {
+    let rt = real_tree();
+    let rtc = real_tree_count();
+    assert_same_result!(rt => unique_roles, rtc => unique_roles);
+    assert_same_result!(rt => unique_roles_first_occurrence, rtc => unique_roles_first_occurrence);
+    assert_same_result!(rt => max_depth, rtc => max_depth);
+    // both trees are built from the same nodes in the same order, so IDs are interchangeable
+    for node_id in rt.root().descendants(&rt.inner).step_by(97) {
+        assert_same_result!(rt => role_counts_in, rtc => role_counts_in, node_id);
+    }
+}
+
+#[test]
+fn validate_wide_siblings() {
+    let leaf = |role| A11yNode {
+        role,
+        name: None,
+        toolkit: None,
+        children: vec![],
+    };
+    // skipping this many siblings one recursive call at a time overflows the stack
+    let mut children = vec![leaf(Role::Label); 200_000];
+    children.push(leaf(Role::Link));
+    let mut t = Tree::from_root_node(A11yNode {
+        role: Role::Frame,
+        name: None,
+        toolkit: None,
+        children,
+    });
+    t.build_rolesets();
+    assert!(t.find_first_roleset(Role::Link).is_some());
+    assert_eq!(t.how_many_roleset(Role::Link), 1);
+}
+
+#[test]
+fn validate_deep_chain() {
+    let mut t = Tree::from_root_node_with(
+        A11yNode {
+            role: Role::Frame,
+            name: None,
+            toolkit: None,
+            children: vec![],
+        },
+        TreeConfig::NONE,
+    );
+    // counting this deep one recursive call per level overflows the stack
+    let mut parent = t.root;
+    for i in 0..100_000 {
+        let role = if i % 2 == 0 { Role::Label } else { Role::Link };
+        let id = t.inner.new_node(Node {
+            role,
+            roleset: RoleSet::EMPTY,
+            flags: FlagWord::default(),
+        });
+        parent.append(id, &mut t.inner);
+        parent = id;
+    }
+    // bottom-up in one pass; `build_rolesets` visits every node's ancestors, which is quadratic
+    // in a chain
+    let mut below = RoleSet::EMPTY;
+    for id in t
+        .root
+        .descendants(&t.inner)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        below |= t.inner[id].get().role;
+        t.inner[id].get_mut().roleset = below;
+    }
+    t.config.rolesets = true;
+    assert_eq!(t.how_many_roleset(Role::Link), 50_000);
+    assert_eq!(t.how_many_roleset(Role::Label), 50_000);
+}
+
+#[test]
+fn validate_counts_small_tree() {
+    let leaf = |role| A11yNode {
+        role,
+        name: None,
+        toolkit: None,
+        children: vec![],
+    };
+    let root = A11yNode {
+        role: Role::Frame,
+        name: None,
+        toolkit: None,
+        children: vec![leaf(Role::Link), leaf(Role::Link), leaf(Role::Heading)],
+    };
+    let mut tc = TreeCount::from_root_node(root);
+    tc.build_rolesets();
+    // each node is counted once, not once more as its own ancestor
+    assert_eq!(tc.how_many_roleset(Role::Frame), 1);
+    assert_eq!(tc.how_many_roleset(Role::Link), 2);
+    assert_eq!(tc.how_many_roleset(Role::Heading), 1);
+    // rebuilding replaces the counts rather than adding to them
+    tc.build_rolesets();
+    assert_eq!(tc.how_many_roleset(Role::Link), 2);
+    assert_eq!(tc.how_many_roleset(Role::Heading), 1);
+}
+
+#[test]
+fn validate_rebuild_dropped_rolesets() {
+    let mut t = Tree::from_root_node_with(real_tree_nodes().clone(), TreeConfig::ALL);
+    t.drop_index(Index::Rolesets);
+    // removed without rolesets, so the ancestors' rolesets are not updated
+    let links = t
+        .root
+        .descendants(&t.inner)
+        .filter(|id| t.inner[*id].get().role == Role::Link)
+        .collect::<Vec<_>>();
+    for link in links {
+        if !t.inner[link].is_removed() {
+            t.remove_subtree(link);
+        }
+    }
+    t.build_rolesets();
+    assert!(!t.inner[t.root].get().roleset.contains(Role::Link.into()));
+    assert!(t.find_first_roleset(Role::Link).is_none());
+    check_laws(&t);
+}
+
+#[test]
+fn validate_same_structure() {
+    assert_tree_eq!(real_tree(), real_tree_count());
+}
+
+#[test]
+fn validate_project() {
+    let rt = real_tree();
+    let roles = RoleSet::EMPTY | Role::Heading | Role::Link;
+    let projected = rt.project(roles);
+    assert!(projected.nodes() < rt.nodes());
+    assert_eq!(projected.config(), rt.config());
+    for role in roles.role_iter() {
+        assert_eq!(projected.how_many_roleset(role), rt.how_many_roleset(role));
+    }
+    // other roles are only kept as ancestors
+    for leaf in projected.iter_leafs() {
+        assert!(roles.contains(leaf.get().role.into()));
+    }
+}
+
+#[test]
+fn validate_path() {
+    let rt = real_tree();
+    assert!(rt.path(rt.root()).is_empty());
+    for node_id in rt.root().descendants(&rt.inner).step_by(97) {
+        let found = rt.path(node_id).into_iter().fold(rt.root(), |id, idx| {
+            id.children(&rt.inner).nth(idx).expect("Valid child index")
+        });
+        assert_eq!(found, node_id);
+        assert_eq!(rt.node_id(&rt.inner[node_id]), Some(node_id));
+    }
+}
+
+#[test]
+fn validate_config() {
+    let rt = real_tree();
+    for config in [TreeConfig::ALL, TreeConfig::NONE] {
+        let t = Tree::from_root_node_with(real_tree_nodes().clone(), config);
+        assert_eq!(t.config(), config);
+        assert_tree_eq!(rt, &t);
+        assert_eq!(rt.max_depth(), t.max_depth());
+        for node_id in t.root().descendants(&t.inner).step_by(97) {
+            assert_same_result!(rt => role_counts_in, &t => role_counts_in, node_id);
+            assert_eq!(node_id.ancestors(&t.inner).count(), t.depth(node_id));
+        }
+    }
+    let t = Tree::from_root_node_with(real_tree_nodes().clone(), TreeConfig::ALL);
+    for role in rt.unique_roles().role_iter() {
+        assert_same_result!(rt => how_many, &t => how_many_roleset, role);
+        assert_same_result!(rt => find_first, &t => find_first_cached, role);
+    }
+}
+
+/// The paths of `nodes`; for comparing trees whose nodes differ in their indexes.
+fn paths<'a, T: TreeTraversal + 'a>(
+    t: &T,
+    nodes: impl IntoIterator<Item = &'a indextree::Node<T::Node>>,
+) -> Vec<Vec<usize>> {
+    nodes
+        .into_iter()
+        .map(|node| t.path(t.node_id(node).expect("Node is in tree")))
+        .collect()
+}
+
+#[test]
+fn validate_fallback() {
+    let rt = real_tree();
+    let t = Tree::from_root_node(real_tree_nodes().clone());
+    let tc = TreeCount::from_root_node(real_tree_nodes().clone());
+    assert_eq!(t.config(), TreeConfig::NONE);
+    assert!(!tc.config().rolesets);
+    let roles = rt.unique_roles() | Role::Calendar;
+    for role in roles.role_iter() {
+        let expected = paths(rt, rt.find_first(role));
+        assert_eq!(expected, paths(&t, t.find_first_roleset(role)), "{role:?}");
+        assert_eq!(
+            expected,
+            paths(&t, t.par_find_first_roleset(role)),
+            "{role:?}"
+        );
+        assert_eq!(expected, paths(&t, t.find_first_stack(role)), "{role:?}");
+        assert_eq!(expected, paths(&t, t.find_first_cached(role)), "{role:?}");
+        assert_eq!(
+            expected,
+            paths(&tc, tc.find_first_roleset(role)),
+            "{role:?}"
+        );
+        assert_same_result!(rt => how_many, &t => how_many_roleset, role);
+        assert_same_result!(rt => how_many, &t => par_how_many_roleset, role);
+        assert_same_result!(rt => how_many, &tc => how_many_roleset, role);
+        assert_same_result!(rt => how_many, &tc => par_how_many_roleset, role);
+    }
+    assert_same_result!(rt => unique_roles_roleset, &t => unique_roles_roleset);
+    assert_same_result!(rt => role_counts_in, &tc => role_counts_in, rt.root());
+    let common = RoleSet::EMPTY | Role::Section | Role::Static | Role::Paragraph | Role::Link;
+    assert_eq!(
+        paths(rt, rt.iter_without_roleset(common)),
+        paths(&t, t.iter_without_roleset(common))
+    );
+    assert_eq!(
+        paths(rt, rt.iter_lacking(common)),
+        paths(&t, t.iter_lacking(common))
+    );
+    assert_eq!(
+        walk(rt, Role::Heading, Tree::find_next),
+        walk(&t, Role::Heading, Tree::find_next)
+    );
+}
+
+/// Every node with `role` after the root, found by repeatedly calling `next`.
+fn walk<T: TreeTraversal>(
+    t: &T,
+    role: Role,
+    next: impl for<'a> Fn(&'a T, indextree::NodeId, Role) -> Option<&'a indextree::Node<T::Node>>,
+) -> Vec<indextree::NodeId> {
+    std::iter::successors(Some(t.root()), |from| {
+        next(t, *from, role).and_then(|node| t.node_id(node))
+    })
+    .skip(1)
+    .collect()
+}
+
+#[test]
+fn validate_find_next() {
+    let rt = real_tree();
+    let rtc = real_tree_count();
+    // `find_next` from every node, not just from each match as in the laws
+    for role in [Role::Heading, Role::Link] {
+        for from in rt.root().descendants(&rt.inner).step_by(97) {
+            assert_eq!(
+                paths(rt, rt.find_next(from, role)),
+                paths(rtc, rtc.find_next(from, role)),
+                "{from:?} ({role:?})"
+            );
+        }
+    }
+    assert!(rt.skip_list_bytes() >= rt.unique_roles().len() * rt.nodes());
+    assert_eq!(walk(rt, Role::Calendar, Tree::find_next_cached), vec![]);
+}
+
+#[test]
+fn validate_traced() {
+    let rt = real_tree();
+    for role in [Role::Heading, Role::Link, Role::Calendar] {
+        let scan = rt.find_first_traced(role);
+        let pruned = rt.find_first_roleset_traced(role);
+        assert_eq!(scan.found, pruned.found, "{role:?}");
+        assert_eq!(
+            pruned.found,
+            rt.find_first_roleset(role).and_then(|n| rt.node_id(n))
+        );
+        // the scan visits everything before the match, in order
+        assert_eq!(scan.runs(rt), [0..=scan.visited.len() - 1], "{role:?}");
+        // every subtree without the role is skipped, except the root
+        for id in pruned.visited.iter().skip(1) {
+            assert!(
+                rt.inner[*id].get().roleset().contains(role.into()),
+                "{role:?}"
+            );
+        }
+        assert!(pruned.visited.len() < scan.visited.len(), "{role:?}");
+        // the stack visits the same nodes as the walker, and the table none
+        assert_eq!(rt.find_first_stack_traced(role), pruned, "{role:?}");
+        let cached = rt.find_first_cached_traced(role);
+        assert_eq!(cached.found, pruned.found, "{role:?}");
+        assert!(cached.visited.is_empty(), "{role:?}");
+    }
+    // the small application has no headings, so finding one never enters it
+    let apps = rt.root().children(&rt.inner).collect::<Vec<_>>();
+    let pruned = rt.find_first_roleset_traced(Role::Heading);
+    assert!(pruned.skipped(rt, apps[0]));
+    assert!(!pruned.skipped(rt, apps[1]));
+}
+
+/// Check that the indexes of a mutated tree match those of a freshly indexed one, and that the
+/// laws still hold.
+fn check_indexes(t: &Tree) {
+    let ids = t.root().descendants(&t.inner).collect::<Vec<_>>();
+    assert_eq!(t.nodes(), ids.len());
+    let mut expected = std::collections::HashMap::new();
+    for id in ids.iter().rev() {
+        let roleset = id
+            .children(&t.inner)
+            .fold(RoleSet::from(t.inner[*id].get().role), |rs, child| {
+                rs | expected[&child]
+            });
+        expected.insert(*id, roleset);
+    }
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(expected[id], t.unique_roles_in(*id), "{id:?}");
+        assert_eq!(id.ancestors(&t.inner).count(), t.depth(*id), "{id:?}");
+        if let Some(next) = ids.get(i + 1) {
+            assert!(t.precedes(*id, *next), "{id:?}");
+        }
+    }
+    check_laws(t);
+}
+
+#[test]
+fn validate_mutation() {
+    let mut t = Tree::from_root_node_with(
+        real_tree_nodes().clone(),
+        TreeConfig {
+            skip_lists: RoleSet::EMPTY | Role::Heading | Role::Calendar | Role::Link,
+            ..TreeConfig::ALL
+        },
+    );
+    // removing the first heading's subtree moves the first occurrence of every role in it
+    let heading = t.find_first(Role::Heading).expect("Has a heading");
+    let heading = t.node_id(heading).expect("Node is in tree");
+    t.remove_subtree(heading);
+    check_indexes(&t);
+    let removed = t
+        .root()
+        .descendants(&t.inner)
+        .skip(1)
+        .step_by(4999)
+        .collect::<Vec<_>>();
+    for id in removed {
+        if !id.is_removed(&t.inner) {
+            t.remove_subtree(id);
+        }
+    }
+    check_indexes(&t);
+    // a new role, appended deep in the tree
+    let deep = t
+        .root()
+        .descendants(&t.inner)
+        .max_by_key(|id| id.ancestors(&t.inner).count())
+        .expect("Non-empty tree");
+    let calendar = A11yNode {
+        role: Role::Calendar,
+        name: None,
+        toolkit: None,
+        children: vec![A11yNode {
+            role: Role::Heading,
+            name: None,
+            toolkit: None,
+            children: vec![],
+        }],
+    };
+    t.append_subtree(deep, calendar.clone());
+    // `par_find_first` can no longer scan the arena (checked by the laws)
+    assert!(!t.arena_in_document_order());
+    check_indexes(&t);
+    // an existing role, appended before its current first occurrence
+    let early = t
+        .root()
+        .descendants(&t.inner)
+        .nth(1)
+        .expect("Root has children");
+    t.append_subtree(early, calendar);
+    check_indexes(&t);
+    // compacting renumbers the nodes, moving the indexes with them
+    let roles = t.unique_roles_first_occurrence();
+    let before = t.heap_bytes();
+    assert!(t.shrink_to_fit());
+    assert!(t.heap_bytes() < before);
+    assert_eq!(t.inner.len(), t.nodes());
+    assert!(t.arena_in_document_order());
+    assert_eq!(t.unique_roles_first_occurrence(), roles);
+    check_indexes(&t);
+}
+
+#[test]
+fn validate_names() {
+    let node = |role, name: Option<&str>, children| A11yNode {
+        role,
+        name: name.map(String::from),
+        toolkit: None,
+        children,
+    };
+    let mut t = Tree::from_root_node(node(Role::Frame, None, vec![]));
+    assert!(t.names.is_empty());
+    let list = t.append_subtree(
+        t.root(),
+        node(
+            Role::List,
+            Some("Links"),
+            vec![
+                node(Role::ListItem, Some("One"), vec![]),
+                node(Role::ListItem, Some("Two"), vec![]),
+            ],
+        ),
+    );
+    assert_eq!(t.name(list), Some("Links"));
+    let policy = crate::TextPolicy::default();
+    assert_eq!(t.flatten_text(t.root(), &policy), "One\nTwo");
+    // removed slots are reused; their names must not be
+    t.remove_subtree(list);
+    let panel = t.append_subtree(
+        t.root(),
+        node(Role::Panel, None, vec![node(Role::Label, None, vec![])]),
+    );
+    assert!(panel.descendants(&t.inner).all(|id| t.name(id).is_none()));
+    assert_eq!(t.flatten_text(t.root(), &policy), "");
+    let projected = t.project(Role::Label.into());
+    assert_eq!(projected.nodes(), 3);
+}
+
+#[test]
+fn validate_warm_up() {
+    use std::sync::{mpsc, Arc, RwLock};
+    let tree = Arc::new(RwLock::new(Tree::from_root_node_with(
+        real_tree_nodes().clone(),
+        TreeConfig::default(),
+    )));
+    let roles = RoleSet::EMPTY | Role::Heading | Role::Link;
+    let (tx, rx) = mpsc::channel();
+    Tree::warm_up(&tree, roles, move |built| {
+        tx.send(built).expect("Receiver is alive");
+    });
+    // answered by the fallback or the pointers, depending on how far the warm-up has got
+    let during = walk(
+        &*tree.read().unwrap(),
+        Role::Heading,
+        Tree::find_next_cached,
+    );
+    assert_eq!(rx.recv().expect("Warm-up finishes"), roles);
+    let t = tree.read().unwrap();
+    assert_eq!(t.skip_list_roles(), roles);
+    assert!(t.skip_list_bytes() >= 2 * t.nodes() * size_of::<Option<indextree::NodeId>>());
+    assert_eq!(during, walk(&*t, Role::Heading, Tree::find_next));
+    assert_eq!(
+        walk(&*t, Role::Link, Tree::find_next_cached),
+        walk(&*t, Role::Link, Tree::find_next)
+    );
+}
+
+#[test]
+fn validate_warm_up_dropped() {
+    use std::sync::{mpsc, Arc, RwLock};
+    let leaf = |role| A11yNode {
+        role,
+        name: None,
+        toolkit: None,
+        children: vec![],
+    };
+    let root = A11yNode {
+        role: Role::Frame,
+        name: None,
+        toolkit: None,
+        children: vec![leaf(Role::Heading), leaf(Role::Link)],
+    };
+    let tree = Arc::new(RwLock::new(Tree::from_root_node_with(
+        root,
+        TreeConfig::default(),
+    )));
+    let (tx, rx) = mpsc::channel();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .expect("Able to build a thread pool");
+    // the pool's only thread runs this closure, so the warm-up cannot start until it returns
+    pool.install(|| {
+        Tree::warm_up(
+            &tree,
+            RoleSet::EMPTY | Role::Heading | Role::Link,
+            move |built| tx.send(built).expect("Receiver is alive"),
+        );
+        let t = tree.read().unwrap();
+        // while warming up, queries do not build the lists themselves
+        assert_eq!(
+            t.find_next_cached_planned(t.root(), Role::Heading).1,
+            QueryPlan::Fallback(Index::SkipList(Role::Heading))
+        );
+        drop(t);
+        let mut t = tree.write().unwrap();
+        t.drop_index(Index::SkipList(Role::Heading));
+    });
+    assert_eq!(rx.recv().expect("Warm-up finishes"), Role::Link.into());
+    let t = tree.read().unwrap();
+    assert_eq!(t.skip_list_roles(), Role::Link.into());
+    assert_eq!(
+        t.find_next_cached_planned(t.root(), Role::Link).1,
+        QueryPlan::Lookup(Index::SkipList(Role::Link))
+    );
+    assert_eq!(
+        t.find_next_cached_planned(t.root(), Role::Heading).1,
+        QueryPlan::Pruned
+    );
+    assert_eq!(
+        walk(&*t, Role::Heading, Tree::find_next_cached),
+        walk(&*t, Role::Heading, Tree::find_next)
+    );
+}
+
+#[test]
+fn validate_serde_round_trip() {
+    #[derive(serde::Serialize)]
+    struct OldTree<'a, T> {
+        inner: &'a indextree::Arena<T>,
+        root: indextree::NodeId,
+    }
+    let root: A11yNode = serde_json::from_str(
+        r#"{"role": "Frame", "children": [
+            {"role": "Section", "children": [{"role": "Link", "children": []}]},
+            {"role": "Heading", "children": []},
+            {"role": "Link", "children": []}
+        ]}"#,
+    )
+    .expect("Valid JSON");
+    let t = Tree::from_root_node_with(root.clone(), TreeConfig::ALL);
+    // built skip lists and positions are not serialized, but the roles lists are kept for are
+    assert!(t.find_next_cached(t.root(), Role::Link).is_some());
+    let json = serde_json::to_string(&t).expect("Valid tree");
+    let back: Tree = serde_json::from_str(&json).expect("Valid JSON");
+    assert_eq!(back.config().skip_lists, back.skip_list_roles());
+    assert_eq!(back, t);
+    check_laws(&back);
+    // trees serialized before indexes were configurable only have an arena and a root
+    let json = serde_json::to_string(&OldTree {
+        inner: &t.inner,
+        root: t.root,
+    })
+    .expect("Valid tree");
+    let old: Tree = serde_json::from_str(&json).expect("Valid old tree");
+    assert_eq!(old.config(), TreeConfig::NONE);
+    assert_eq!(old.skip_list_roles(), RoleSet::EMPTY);
+    check_laws(&old);
+    let mut tc = TreeCount::from_root_node(root);
+    tc.build_rolesets();
+    let json = serde_json::to_string(&OldTree {
+        inner: &tc.inner,
+        root: tc.root,
+    })
+    .expect("Valid tree");
+    let old: TreeCount = serde_json::from_str(&json).expect("Valid old tree");
+    assert_eq!(old.config(), TreeConfig::NONE);
+    assert_eq!(
+        old.how_many_roleset_planned(Role::Link),
+        (2, QueryPlan::Fallback(Index::Rolesets))
+    );
+    check_laws(&old);
+}
+
+/// Check that every node's subtree flags are the union of its subtree's own flags.
+fn check_flags(t: &Tree) {
+    for id in t.root().descendants(&t.inner) {
+        let expected = id
+            .descendants(&t.inner)
+            .fold(Flags::EMPTY, |flags, node| flags | t.flags(node));
+        assert_eq!(t.inner[id].get().subtree_flags(), expected);
+    }
+}
+
+#[test]
+fn validate_flags() {
+    let mut t = Tree::from_root_node_with(real_tree_nodes().clone(), TreeConfig::default());
+    let ids = t.root().descendants(&t.inner).collect::<Vec<_>>();
+    for id in ids.iter().step_by(997) {
+        t.set_flags(*id, Flags::VISITED);
+    }
+    for id in ids.iter().step_by(1999) {
+        t.set_flags(*id, Flags::MARKED | Flags::custom(1));
+    }
+    check_flags(&t);
+    for flags in [
+        Flags::VISITED,
+        Flags::MARKED | Flags::custom(1),
+        Flags::STALE,
+    ] {
+        assert_eq!(
+            t.iter_flagged(flags).collect::<Vec<_>>(),
+            ids.iter()
+                .copied()
+                .filter(|id| t.flags(*id).contains(flags))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            t.iter_without_flags(flags).collect::<Vec<_>>(),
+            ids.iter()
+                .copied()
+                .filter(|id| !t.flags(*id).intersects(flags))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            t.iter_lacking_flags(flags).collect::<Vec<_>>(),
+            ids.iter()
+                .copied()
+                .filter(|id| !id
+                    .descendants(&t.inner)
+                    .any(|d| t.flags(d).intersects(flags)))
+                .collect::<Vec<_>>()
+        );
+    }
+    for id in ids.iter().step_by(997 * 2) {
+        t.clear_flags(*id, Flags::VISITED);
+    }
+    check_flags(&t);
+    let flagged = t
+        .iter_flagged(Flags::VISITED)
+        .nth(3)
+        .expect("Has flagged nodes");
+    let removed = t.inner[flagged].parent().expect("Not the root");
+    t.remove_subtree(removed);
+    check_flags(&t);
+}
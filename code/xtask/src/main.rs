@@ -0,0 +1,363 @@
+//! Development tasks for this workspace.
+//!
+//! `cargo xtask bench-history` appends the latest `cargo bench` results to a JSON history, and
+//! fails if any benchmark got slower than the previous run by more than a threshold.
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const USAGE: &str = "Usage:
+    cargo xtask bench-history [--threshold PERCENT] [--history FILE] [--criterion DIR]";
+
+/// Where the history is kept, relative to the workspace.
+const HISTORY: &str = "bench-history.json";
+/// Where criterion writes its results, relative to the workspace.
+const CRITERION: &str = "target/criterion";
+/// How much slower (in percent) a benchmark may get before it is a regression.
+const THRESHOLD: f64 = 10.0;
+
+struct Args {
+    /// How much slower (in percent) a benchmark may get before it is a regression.
+    threshold: f64,
+    history: PathBuf,
+    criterion: PathBuf,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .expect("xtask is in the workspace");
+        let mut args = env::args().skip(1);
+        if args.next().as_deref() != Some("bench-history") {
+            return Err(USAGE.into());
+        }
+        let mut parsed = Args {
+            threshold: THRESHOLD,
+            history: workspace.join(HISTORY),
+            criterion: workspace.join(CRITERION),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(USAGE);
+            match arg.as_str() {
+                "--threshold" => parsed.threshold = value()?.parse()?,
+                "--history" => parsed.history = value()?.into(),
+                "--criterion" => parsed.criterion = value()?.into(),
+                _ => return Err(format!("Unexpected argument: {arg}\n{USAGE}").into()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// One `cargo bench` run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Run {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    /// The commit benchmarked, if known.
+    commit: Option<String>,
+    /// Results by criterion's full benchmark ID.
+    results: BTreeMap<String, Summary>,
+}
+
+/// The summary of one benchmark in a [`Run`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Summary {
+    /// The first part of the group (e.g., `real`, `synth`, or `scenario`).
+    dataset: String,
+    /// The rest of the group (e.g., `tree/sequential`).
+    backend: String,
+    /// The benchmarked function, and its parameter if any.
+    method: String,
+    mean_ns: f64,
+    /// The mean's 95% confidence interval.
+    lower_ns: f64,
+    upper_ns: f64,
+    /// When criterion wrote the result; the same in a later run if the benchmark was not re-run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<SystemTime>,
+}
+
+/// The parts of criterion's `benchmark.json` which are needed.
+#[derive(Deserialize)]
+struct Benchmark {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    confidence_interval: ConfidenceInterval,
+    point_estimate: f64,
+}
+
+#[derive(Deserialize)]
+struct ConfidenceInterval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+/// Find every `new/` result under `dir` which criterion has written since it was last recorded
+/// (when it was last modified, by benchmark ID, in `seen`), so that benchmarks which were not
+/// re-run are not recorded again.
+///
+/// Modification times are compared exactly, rather than against the time of the last run, so
+/// results written within the same second as a run are not missed or recorded twice.
+fn read_results(
+    dir: &Path,
+    seen: &BTreeMap<String, SystemTime>,
+    results: &mut BTreeMap<String, Summary>,
+) -> Result<()> {
+    let estimates = dir.join("new").join("estimates.json");
+    if estimates.is_file() {
+        let modified = fs::metadata(&estimates)?.modified()?;
+        let benchmark: Benchmark =
+            serde_json::from_str(&fs::read_to_string(dir.join("new").join("benchmark.json"))?)?;
+        if seen.get(&benchmark.full_id) != Some(&modified) {
+            let estimates: Estimates = serde_json::from_str(&fs::read_to_string(&estimates)?)?;
+            let (dataset, backend) = benchmark
+                .group_id
+                .split_once('/')
+                .unwrap_or((&benchmark.group_id, ""));
+            let method = [benchmark.function_id, benchmark.value_str]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("/");
+            results.insert(
+                benchmark.full_id,
+                Summary {
+                    dataset: dataset.to_string(),
+                    backend: backend.to_string(),
+                    method,
+                    mean_ns: estimates.mean.point_estimate,
+                    lower_ns: estimates.mean.confidence_interval.lower_bound,
+                    upper_ns: estimates.mean.confidence_interval.upper_bound,
+                    modified: Some(modified),
+                },
+            );
+        }
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // criterion's HTML reports are not results
+        if path.is_dir() && path.file_name().is_some_and(|name| name != "report") {
+            read_results(&path, seen, results)?;
+        }
+    }
+    Ok(())
+}
+
+/// A benchmark which got slower than in the previous run.
+#[derive(Debug, PartialEq)]
+struct Regression<'a> {
+    id: &'a str,
+    before_ns: f64,
+    after_ns: f64,
+}
+
+impl Regression<'_> {
+    fn percent(&self) -> f64 {
+        (self.after_ns / self.before_ns - 1.0) * 100.0
+    }
+}
+
+/// Every benchmark in `run` whose mean is more than `threshold` percent slower than the most
+/// recent earlier run of it in `history`.
+fn regressions<'a>(history: &'a [Run], run: &'a Run, threshold: f64) -> Vec<Regression<'a>> {
+    run.results
+        .iter()
+        .filter_map(|(id, summary)| {
+            let before = history.iter().rev().find_map(|run| run.results.get(id))?;
+            let regression = Regression {
+                id,
+                before_ns: before.mean_ns,
+                after_ns: summary.mean_ns,
+            };
+            (regression.percent() > threshold).then_some(regression)
+        })
+        .collect()
+}
+
+fn commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn bench_history(args: &Args) -> Result<bool> {
+    let mut history: Vec<Run> = match fs::read_to_string(&args.history) {
+        Ok(data) => serde_json::from_str(&data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    // later runs come last, so each benchmark ends up with its latest result
+    let seen = history
+        .iter()
+        .flat_map(|run| &run.results)
+        .filter_map(|(id, summary)| Some((id.clone(), summary.modified?)))
+        .collect::<BTreeMap<_, _>>();
+    let mut run = Run {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        commit: commit(),
+        results: BTreeMap::new(),
+    };
+    read_results(&args.criterion, &seen, &mut run.results)?;
+    if run.results.is_empty() {
+        return Err(format!(
+            "No benchmark results in {} since the last run; run `cargo bench` first",
+            args.criterion.display()
+        )
+        .into());
+    }
+    let regressions = regressions(&history, &run, args.threshold);
+    for regression in &regressions {
+        println!(
+            "REGRESSION {}: {:.0}ns -> {:.0}ns (+{:.1}%)",
+            regression.id,
+            regression.before_ns,
+            regression.after_ns,
+            regression.percent()
+        );
+    }
+    println!(
+        "Recorded {} benchmarks in {} ({} regressions over {}%)",
+        run.results.len(),
+        args.history.display(),
+        regressions.len(),
+        args.threshold
+    );
+    let passed = regressions.is_empty();
+    history.push(run);
+    fs::write(&args.history, serde_json::to_string_pretty(&history)?)?;
+    Ok(passed)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+    if !bench_history(&args)? {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_results, regressions, Regression, Run, Summary};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+    use std::{env, fs, process};
+
+    fn run(timestamp: u64, means: &[(&str, f64)]) -> Run {
+        Run {
+            timestamp,
+            commit: None,
+            results: means
+                .iter()
+                .map(|(id, mean_ns)| {
+                    let summary = Summary {
+                        dataset: "real".to_string(),
+                        backend: "tree/sequential".to_string(),
+                        method: (*id).to_string(),
+                        mean_ns: *mean_ns,
+                        lower_ns: *mean_ns,
+                        upper_ns: *mean_ns,
+                        modified: None,
+                    };
+                    ((*id).to_string(), summary)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn regressions_against_latest_run() {
+        let history = [
+            run(1, &[("find_first", 100.0), ("how_many", 100.0)]),
+            run(2, &[("find_first", 200.0)]),
+        ];
+        let latest = run(
+            3,
+            &[("find_first", 215.0), ("how_many", 120.0), ("new", 1.0)],
+        );
+        // `find_first` is compared with run 2, `how_many` with run 1, and `new` with nothing
+        assert_eq!(
+            regressions(&history, &latest, 10.0),
+            vec![Regression {
+                id: "how_many",
+                before_ns: 100.0,
+                after_ns: 120.0
+            }]
+        );
+        assert!(regressions(&history, &latest, 25.0).is_empty());
+    }
+    #[test]
+    fn results_recorded_once() {
+        let dir = env::temp_dir().join(format!("xtask-criterion-{}", process::id()));
+        let new = dir.join("real_tree").join("find_first").join("new");
+        fs::create_dir_all(&new).expect("Able to create results");
+        fs::create_dir_all(dir.join("report")).expect("Able to create report");
+        fs::write(
+            new.join("benchmark.json"),
+            r#"{"group_id": "real/tree", "function_id": "find_first", "value_str": null,
+                "full_id": "real/tree/find_first"}"#,
+        )
+        .expect("Able to write benchmark");
+        let write_estimates = |mean: f64| {
+            fs::write(
+                new.join("estimates.json"),
+                format!(
+                    r#"{{"mean": {{"point_estimate": {mean},
+                        "confidence_interval": {{"lower_bound": {mean}, "upper_bound": {mean}}}}}}}"#
+                ),
+            )
+            .expect("Able to write estimates");
+        };
+        let read = |seen: &BTreeMap<String, SystemTime>| {
+            let mut results = BTreeMap::new();
+            read_results(&dir, seen, &mut results).expect("Valid results");
+            results
+        };
+        write_estimates(100.0);
+        let first = read(&BTreeMap::new());
+        assert_eq!(first["real/tree/find_first"].method, "find_first");
+        let seen = first
+            .iter()
+            .map(|(id, summary)| (id.clone(), summary.modified.expect("Valid time")))
+            .collect();
+        assert!(read(&seen).is_empty());
+        // re-run within the same second as the first
+        write_estimates(120.0);
+        fs::File::options()
+            .write(true)
+            .open(new.join("estimates.json"))
+            .and_then(|file| {
+                file.set_modified(seen["real/tree/find_first"] + Duration::from_millis(1))
+            })
+            .expect("Able to set modified time");
+        assert_eq!(read(&seen)["real/tree/find_first"].mean_ns, 120.0);
+        fs::remove_dir_all(&dir).expect("Able to clean up");
+    }
+}